@@ -1,8 +1,8 @@
 #![allow(dead_code)]
 use crate::{
-    create_one,
+    check_ok_500, create_one,
     database::repository::Repository,
-    find_one, insert_one,
+    find_many, find_one, insert_one,
     models::{
         admin::ADMIN,
         atm::ATM,
@@ -10,9 +10,9 @@ use crate::{
         token::{JWT, TOKEN},
         user::ACCOUNT,
     },
-    update_one,
+    option, update_one,
 };
-use mongodb::bson::{doc, oid::ObjectId};
+use mongodb::bson::{doc, oid::ObjectId, Bson, Document};
 use rocket::http::Status;
 
 impl Repository {
@@ -60,7 +60,12 @@ impl Repository {
     // Throws 404 and 500
     pub async fn login_admin(&self, sub: ObjectId, username: String) -> Result<String, Status> {
         let time = timestamp_millis();
-        let token = TOKEN::new(sub.to_string(), "Admin".to_string()).unwrap();
+        let token = TOKEN::new(
+            sub.to_string(),
+            "Admin".to_string(),
+            self.settings.access_token_expiry,
+        )
+        .unwrap();
 
         println!("Token Created in time {}", timestamp_millis() - time);
         let mut jwt = JWT::token_to_jwt(&token, &self.keys)?;
@@ -84,4 +89,57 @@ impl Repository {
             Err(error) => return Err(error),
         }
     }
+
+    // Throws 500
+    pub async fn get_admins(&self) -> Result<Vec<ADMIN>, Status> {
+        let filter = doc! {};
+        find_many!(&self.admin, None, filter)
+    }
+
+    // Throws 404 and 500, the admin is signed out so the change applies at once
+    pub async fn update_admin(
+        &self,
+        creator: ObjectId,
+        admin: &ADMIN,
+        field: &str,
+        value: Bson,
+        (old, new): (Bson, Bson), // Logged, so secrets can be sealed
+    ) -> Result<(), Status> {
+        let id = option!(val -> admin.id; {val} | {return Err(Status::NotFound)});
+        let mut set = Document::new();
+        set.insert(field, value);
+        let update = doc! {
+            "$set": set
+        };
+        let result = update_one!(&self.admin, update, None, ("_id", id))?;
+        if result.matched_count == 0 {
+            return Err(Status::NotFound);
+        }
+        self.revoke_admin_token(admin).await?;
+
+        self.log_update(creator, "Admin", id, field, old, new).await;
+        Ok(())
+    }
+
+    // Throws 500, the token and every refresh token stop working as soon as they are gone
+    pub async fn revoke_admin_token(&self, admin: &ADMIN) -> Result<(), Status> {
+        if let Some(id) = admin.id {
+            self.revoke_refresh_tokens(id).await?;
+        }
+        let token = match admin.token {
+            Some(token) => token,
+            None => return Ok(()),
+        };
+        check_ok_500!(
+            self.token.delete_one(doc! { "_id": token }, None),
+            "Revoking Token"
+        )?;
+        let update = doc! {
+            "$unset": {
+                "token": ""
+            }
+        };
+        update_one!(&self.admin, update, None, ("username", &admin.username))?;
+        Ok(())
+    }
 }
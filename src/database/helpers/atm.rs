@@ -1,14 +1,18 @@
 use crate::{
-    find_one,
+    check_ok_500, check_result, find_one,
     models::{
-        atm::ATM,
-
+        atm::{
+            AtmState, AtmUpdate, CassetteAdjustment, Location, ATM, CASSETTE, HEARTBEAT, NEARBY,
+        },
+        helpers::common::timestamp_millis,
         token::{JWT, TOKEN},
     },
-    update_one,
+    option, update_one, update_one_with_session,
+    utilities::crypto::hash_password_default,
 };
 use mongodb::{
-    bson::{doc, oid::ObjectId},
+    bson::{doc, from_document, oid::ObjectId, to_bson, Bson, Document},
+    sync::ClientSession,
 };
 use rocket::http::Status;
 
@@ -23,8 +27,276 @@ impl Repository {
         find_one!(&self.atm, None, _id => id)
     }
 
+    // Closest first, the 2dsphere index has to exist for $geoNear
+    pub async fn get_nearest_atms(
+        &self,
+        location: &Location,
+        filter: Document,
+        limit: i64,
+    ) -> Result<Vec<NEARBY>, Status> {
+        let pipeline = vec![
+            doc! {
+                "$geoNear": {
+                    "near": {
+                        "type": Location::GEOJSON_TYPE,
+                        "coordinates": location.to_geojson().to_vec()
+                    },
+                    "distanceField": "distance",
+                    "spherical": true,
+                    "query": filter
+                }
+            },
+            doc! {
+                "$limit": limit
+            },
+            doc! {
+                "$project": {
+                    "name": 1,
+                    "branch": 1,
+                    "address": 1,
+                    "coordinates": 1,
+                    "cassettes": 1,
+                    "state": 1,
+                    "heartbeat": 1,
+                    "distance": 1
+                }
+            },
+        ];
+        let cursor = check_ok_500!(self.atm.aggregate(pipeline, None))?;
+        let mut atms = Vec::new();
+        for result in cursor {
+            let document = check_ok_500!(result)?;
+            let atm: NEARBY = check_ok_500!(from_document(document), "Reading ATM")?;
+            atms.push(atm.with_status(&self.settings));
+        }
+        Ok(atms)
+    }
+
+    // Throws 404 and 500, returns the state the ATM is in now
+    pub async fn record_heartbeat(
+        &self,
+        atm: &ATM,
+        mut heartbeat: HEARTBEAT,
+    ) -> Result<AtmState, Status> {
+        let id = option!(val -> atm.id; {val} | {return Err(Status::NotFound)});
+        heartbeat.timestamp = timestamp_millis();
+        let state = match atm.state {
+            AtmState::OUT_OF_SERVICE => AtmState::OUT_OF_SERVICE,
+            _ => heartbeat.health(&self.settings),
+        };
+        let update = doc! {
+            "$set": {
+                "heartbeat": check_ok_500!(to_bson(&heartbeat))?,
+                "state": state.to_string()
+            }
+        };
+        let result = update_one!(&self.atm, update, None, ("_id", id))?;
+        if result.matched_count == 0 {
+            return Err(Status::NotFound);
+        }
+
+        // Heartbeats arrive every few minutes, only changes of state are logged
+        let previous = atm.current_state(&self.settings);
+        if previous != state {
+            let (old, new) = (previous.to_string(), state.to_string());
+            self.log_update(id, "ATM", id, "state", Bson::String(old), Bson::String(new))
+                .await;
+        }
+        Ok(state)
+    }
+
+    // Throws 404 and 500, returns the state the ATM is in now, going out of service rejects what is pending
+    pub async fn set_out_of_service(
+        &self,
+        creator: ObjectId,
+        atm: &ATM,
+        out_of_service: bool,
+    ) -> Result<AtmState, Status> {
+        let id = option!(val -> atm.id; {val} | {return Err(Status::NotFound)});
+        // Back in service the last heartbeat decides until the next one arrives
+        let state = match (out_of_service, &atm.heartbeat) {
+            (true, _) => AtmState::OUT_OF_SERVICE,
+            (false, Some(heartbeat)) => heartbeat.health(&self.settings),
+            (false, None) => AtmState::OFFLINE,
+        };
+        let update = doc! {
+            "$set": {
+                "state": state.to_string()
+            }
+        };
+        let result = update_one!(&self.atm, update, None, ("_id", id))?;
+        if result.matched_count == 0 {
+            return Err(Status::NotFound);
+        }
+        if out_of_service {
+            self.reject_all_pending_txn("atm", &atm.name, Some(creator), "Admin")
+                .await?;
+        }
+
+        let old = Bson::String(atm.current_state(&self.settings).to_string());
+        let new = Bson::String(state.to_string());
+        self.log_update(creator, "Admin", id, "state", old, new)
+            .await;
+        Ok(AtmState::current(
+            state,
+            atm.heartbeat.as_ref(),
+            &self.settings,
+        ))
+    }
+
+    // Throws 404 and 500, every changed field is logged, a new password signs the ATM out
+    pub async fn update_atm(
+        &self,
+        creator: ObjectId,
+        atm: &ATM,
+        changes: AtmUpdate,
+    ) -> Result<(), Status> {
+        let id = option!(val -> atm.id; {val} | {return Err(Status::NotFound)});
+        let mut set = Document::new();
+        let mut logged: Vec<(&str, Bson, Bson)> = Vec::new();
+
+        if let Some(branch) = changes.branch {
+            logged.push((
+                "branch",
+                Bson::String(atm.branch.clone()),
+                Bson::String(branch.clone()),
+            ));
+            set.insert("branch", branch);
+        }
+        if let Some(address) = changes.address {
+            logged.push((
+                "address",
+                Bson::String(atm.address.clone()),
+                Bson::String(address.clone()),
+            ));
+            set.insert("address", address);
+        }
+        if let Some(coordinates) = changes.coordinates {
+            let old = to_bson(&atm.coordinates).unwrap_or(Bson::Null);
+            let new = check_ok_500!(to_bson(&coordinates))?;
+            logged.push(("coordinates", old, new.clone()));
+            set.insert("coordinates", new);
+        }
+        let password_changed = changes.password.is_some();
+        if let Some(password) = changes.password {
+            let hash = check_ok_500!(hash_password_default(password), "Hashing")?;
+            let sealed = Bson::String("$SEALED$".to_string());
+            logged.push(("password", sealed.clone(), sealed));
+            set.insert("password", hash);
+        }
+
+        let update = doc! {
+            "$set": set
+        };
+        let result = update_one!(&self.atm, update, None, ("_id", id))?;
+        if result.matched_count == 0 {
+            return Err(Status::NotFound);
+        }
+        if password_changed {
+            self.revoke_atm_token(atm).await?;
+        }
+
+        for (field, old, new) in logged {
+            self.log_update(creator, "Admin", id, field, old, new).await;
+        }
+        Ok(())
+    }
+
+    // Throws 404 and 500, deactivating takes the ATM out of service, signs it out and rejects what is pending
+    pub async fn set_atm_active(
+        &self,
+        creator: ObjectId,
+        atm: &ATM,
+        active: bool,
+    ) -> Result<AtmState, Status> {
+        let id = option!(val -> atm.id; {val} | {return Err(Status::NotFound)});
+        // A reactivated ATM stays OFFLINE until it logs in again and reports a heartbeat
+        let state = match active {
+            true => AtmState::OFFLINE,
+            false => AtmState::OUT_OF_SERVICE,
+        };
+        let update = doc! {
+            "$set": {
+                "active": active,
+                "state": state.to_string()
+            }
+        };
+        let result = update_one!(&self.atm, update, None, ("_id", id))?;
+        if result.matched_count == 0 {
+            return Err(Status::NotFound);
+        }
+        if !active {
+            self.revoke_atm_token(atm).await?;
+            self.reject_all_pending_txn("atm", &atm.name, Some(creator), "Admin")
+                .await?;
+        }
+
+        let old_state = Bson::String(atm.current_state(&self.settings).to_string());
+        let (old, new) = (Bson::Boolean(atm.active), Bson::Boolean(active));
+        self.log_update(creator, "Admin", id, "active", old, new)
+            .await;
+        self.log_update(
+            creator,
+            "Admin",
+            id,
+            "state",
+            old_state,
+            Bson::String(state.to_string()),
+        )
+        .await;
+        Ok(state)
+    }
+
+    // Throws 404 and 500, the token and every refresh token stop working as soon as they are gone
+    pub async fn revoke_atm_token(&self, atm: &ATM) -> Result<(), Status> {
+        if let Some(id) = atm.id {
+            self.revoke_refresh_tokens(id).await?;
+        }
+        let token = match atm.token {
+            Some(token) => token,
+            None => return Ok(()),
+        };
+        check_ok_500!(
+            self.token.delete_one(doc! { "_id": token }, None),
+            "Revoking Token"
+        )?;
+        let update = doc! {
+            "$unset": {
+                "token": ""
+            }
+        };
+        update_one!(&self.atm, update, None, ("name", &atm.name))?;
+        Ok(())
+    }
+
+    // Throws 404 and 500, the transactions of the ATM are kept and the removed ATM is logged
+    pub async fn delete_atm(&self, creator: ObjectId, atm: &ATM) -> Result<(), Status> {
+        let id = option!(val -> atm.id; {val} | {return Err(Status::NotFound)});
+        self.revoke_atm_token(atm).await?;
+        let result = check_ok_500!(
+            self.atm.delete_one(doc! { "_id": id }, None),
+            "Deleting ATM"
+        )?;
+        if result.deleted_count == 0 {
+            return Err(Status::NotFound);
+        }
+
+        let mut removed = atm.clone();
+        removed.password = "$SEALED$".to_string();
+        removed.token = None;
+        let old = to_bson(&removed).unwrap_or(Bson::Null);
+        self.log_update(creator, "Admin", id, "atm", old, Bson::Null)
+            .await;
+        Ok(())
+    }
+
     pub async fn login_atm(&self, sub: ObjectId, name: String) -> Result<String, Status> {
-        let token = TOKEN::new(sub.to_string(), "Atm".to_string()).unwrap();
+        let token = TOKEN::new(
+            sub.to_string(),
+            "Atm".to_string(),
+            self.settings.access_token_expiry,
+        )
+        .unwrap();
         let mut jwt = JWT::token_to_jwt(&token, &self.keys)?;
         let id = self.insert_token(jwt.clone(), sub).await?;
         jwt.set_id(id);
@@ -43,4 +315,151 @@ impl Repository {
             Err(error) => return Err(error),
         }
     }
+
+    // Replaces the whole inventory, used when cassettes are swapped
+    pub async fn load_cassettes(
+        &self,
+        creator: ObjectId,
+        name: &str,
+        cassettes: Vec<CASSETTE>,
+    ) -> Result<(), Status> {
+        let atm = self.get_atm(name.to_string()).await?;
+        let id = option!(val -> atm.id; {val} | {return Err(Status::NotFound)});
+        let old = to_bson(&atm.cassettes).unwrap_or(Bson::Null);
+        let new = check_ok_500!(to_bson(&cassettes))?;
+
+        let update = doc! {
+            "$set": {
+                "cassettes": new.clone()
+            }
+        };
+        update_one!(&self.atm, update, None, ("_id", id))?;
+        self.log_update(creator, "Admin", id, "cassettes", old, new)
+            .await;
+        Ok(())
+    }
+
+    // Throws 404 when the ATM is missing and 406 when too many notes would be removed
+    pub async fn adjust_cassette(
+        &self,
+        creator: ObjectId,
+        name: &str,
+        adjustment: CassetteAdjustment,
+    ) -> Result<(), Status> {
+        let atm = self.get_atm(name.to_string()).await?;
+        let id = option!(val -> atm.id; {val} | {return Err(Status::NotFound)});
+        let (denomination, delta) = (adjustment.denomination, adjustment.delta);
+        let old = atm
+            .cassettes
+            .iter()
+            .find(|cassette| cassette.denomination == denomination)
+            .map(|cassette| cassette.count);
+
+        let result = match old {
+            Some(_) => {
+                let query = doc! {
+                    "_id": id,
+                    "cassettes": {
+                        "$elemMatch": {
+                            "denomination": denomination,
+                            "count": { "$gte": -delta }
+                        }
+                    }
+                };
+                let update = doc! {
+                    "$inc": {
+                        "cassettes.$.count": delta
+                    }
+                };
+                update_one!(&self.atm, update, None, query)?
+            }
+            None if delta >= 0 => {
+                let cassette = check_ok_500!(to_bson(&CASSETTE {
+                    denomination,
+                    count: delta
+                }))?;
+                let query = doc! {
+                    "_id": id,
+                    "cassettes.denomination": { "$ne": denomination }
+                };
+                let update = doc! {
+                    "$push": {
+                        "cassettes": cassette
+                    }
+                };
+                update_one!(&self.atm, update, None, query)?
+            }
+            None => return Err(Status::NotAcceptable),
+        };
+        if result.matched_count != 1 {
+            return Err(Status::NotAcceptable);
+        }
+
+        let field = format!("cassettes.{denomination}");
+        let old = old.map(Bson::Int64).unwrap_or(Bson::Null);
+        let new = Bson::Int64(old.as_i64().unwrap_or(0) + delta);
+        self.log_update(creator, "Admin", id, &field, old, new)
+            .await;
+        Ok(())
+    }
+
+    // Removes dispensed notes, all cassettes change together or none do
+    pub async fn record_dispense(&self, name: &str, notes: &[CASSETTE]) -> Result<(), Status> {
+        let mut session = check_ok_500!(self.client.start_session(None), "Starting Session")?;
+        check_ok_500!(session.start_transaction(None), "Starting Dispense")?;
+
+        match self.apply_dispense(&mut session, name, notes) {
+            Ok(_) => check_ok_500!(session.commit_transaction(), "Committing Dispense"),
+            Err(error) => {
+                check_result!(session.abort_transaction(), "Aborting Dispense");
+                Err(error)
+            }
+        }
+    }
+
+    fn apply_dispense(
+        &self,
+        session: &mut ClientSession,
+        name: &str,
+        notes: &[CASSETTE],
+    ) -> Result<(), Status> {
+        for note in notes.iter().filter(|note| note.count > 0) {
+            let query = doc! {
+                "name": name,
+                "cassettes": {
+                    "$elemMatch": {
+                        "denomination": note.denomination,
+                        "count": { "$gte": note.count }
+                    }
+                }
+            };
+            let update = doc! {
+                "$inc": {
+                    "cassettes.$.count": -note.count
+                }
+            };
+            let result = update_one_with_session!(&self.atm, update, None, session, query)?;
+            if result.matched_count != 1 {
+                return Err(Status::NotAcceptable);
+            }
+        }
+        Ok(())
+    }
+}
+
+// Rewrites coordinates stored as latitude and longitude fields into GeoJSON points
+pub fn geojson_migration() -> (Document, Vec<Document>) {
+    let query = doc! {
+        "coordinates.type": { "$exists": false },
+        "coordinates.latitude": { "$exists": true }
+    };
+    let pipeline = vec![doc! {
+        "$set": {
+            "coordinates": {
+                "type": Location::GEOJSON_TYPE,
+                "coordinates": ["$coordinates.longitude", "$coordinates.latitude"]
+            }
+        }
+    }];
+    (query, pipeline)
 }
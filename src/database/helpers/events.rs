@@ -0,0 +1,30 @@
+use crate::{
+    database::repository::Repository,
+    models::{
+        events::TxnEvent,
+        transaction::{TxnStatus, TRANSACTION},
+    },
+};
+use rocket::tokio::sync::broadcast::Receiver;
+
+impl Repository {
+    // Sending only fails when nobody is listening, which is fine
+    pub fn publish(&self, event: TxnEvent) {
+        let _ = self.events.send(event);
+    }
+
+    pub fn publish_txn_event(&self, txn: &TRANSACTION, status: TxnStatus) {
+        self.enqueue_webhooks(txn, status);
+        self.announce_txn_event(txn, status);
+    }
+
+    // For status changes that already stored their webhook deliveries in the same session
+    pub fn announce_txn_event(&self, txn: &TRANSACTION, status: TxnStatus) {
+        self.publish(TxnEvent::new(txn, status));
+        self.notify_txn(txn, status);
+    }
+
+    pub fn subscribe_txn_events(&self) -> Receiver<TxnEvent> {
+        self.events.subscribe()
+    }
+}
@@ -0,0 +1,128 @@
+use crate::{
+    check_ok_500, check_result,
+    database::repository::Repository,
+    find_many, insert_one,
+    models::{
+        atm::ATM,
+        fraud::{FraudRule, History, ASSESSMENT},
+        helpers::common::timestamp_millis,
+        logs::LOG,
+        notification::Notice,
+        transaction::{TxnStatus, TRANSACTION},
+        user::ACCOUNT,
+    },
+    option,
+};
+use mongodb::{
+    bson::{doc, oid::ObjectId, DateTime},
+    options::{FindOneOptions, FindOptions},
+};
+use rocket::http::Status;
+
+impl Repository {
+    // Earlier transactions of the account the fraud rules look at
+    pub async fn get_fraud_history(
+        &self,
+        number: &str,
+        atm: &ATM,
+        txn: &TRANSACTION,
+    ) -> Result<History, Status> {
+        let now = timestamp_millis();
+        let since = DateTime::from_millis(now - FraudRule::VELOCITY_WINDOW);
+        let recent = doc! {
+            "account": number,
+            "created": { "$gte": since }
+        };
+        let recent = check_ok_500!(self.txn.count_documents(recent, None))?;
+
+        let options = FindOptions::builder()
+            .sort(doc! {
+                "created": -1
+            })
+            .limit(FraudRule::HISTORY_SIZE)
+            .build();
+        let completed = doc! {
+            "account": number,
+            "txn_type": txn.txn_type.to_string(),
+            "status": TxnStatus::COMPLETE.to_string()
+        };
+        let completed: Vec<TRANSACTION> = find_many!(&self.txn, options, completed)?;
+        let amounts = completed.iter().map(|txn| txn.amount).collect();
+
+        let uses = doc! {
+            "account": number,
+            "atm": &atm.name,
+            "status": TxnStatus::COMPLETE.to_string()
+        };
+        let previous_uses = check_ok_500!(self.txn.count_documents(uses, None))?;
+
+        // Last transaction started at any other ATM
+        let options = FindOneOptions::builder()
+            .sort(doc! {
+                "created": -1
+            })
+            .build();
+        let elsewhere = doc! {
+            "account": number,
+            "atm": { "$exists": true, "$ne": &atm.name }
+        };
+        let last = check_ok_500!(self.txn.find_one(elsewhere, options))?;
+        let travel = match last.as_ref().and_then(|last| last.atm.clone()) {
+            Some(name) => match self.get_atm(name).await {
+                Ok(previous) => Some((
+                    previous.coordinates.distance(&atm.coordinates),
+                    now - last.unwrap().created.timestamp_millis(),
+                )),
+                Err(_) => None, // Removed since
+            },
+            None => None,
+        };
+
+        Ok(History {
+            recent,
+            amounts,
+            previous_uses,
+            travel,
+        })
+    }
+
+    // Scores the transaction and logs the decision, an allowed transaction keeps the assessment
+    pub async fn screen_txn(
+        &self,
+        creator: Option<ObjectId>,
+        role: &str,
+        account: &ACCOUNT,
+        atm: &ATM,
+        txn: &mut TRANSACTION,
+    ) -> Result<ASSESSMENT, Status> {
+        let number =
+            option!(val -> account.number.as_ref(); {val} | {return Err(Status::NotFound)});
+        let history = self.get_fraud_history(number, atm, txn).await?;
+        let reachable = account.contact.is_some();
+        let assessment = ASSESSMENT::evaluate(&history, txn.amount, reachable, &self.settings);
+
+        let id = ObjectId::new();
+        txn.id = Some(id);
+        txn.risk = Some(assessment.clone());
+
+        let mut log = LOG::new();
+        log.timestamp(timestamp_millis())
+            .role(role)
+            .screening(id, number.to_owned(), &assessment);
+        if let Some(creator) = creator {
+            log.creator(creator);
+        }
+        let log = log.build();
+        check_result!(insert_one!(&self.logs, log, None), "Logging");
+        Ok(assessment)
+    }
+
+    // The challenge only goes out after the transaction was stored
+    pub fn send_challenge(&self, account: &ACCOUNT, txn: &TRANSACTION, challenge: String) {
+        let body = format!(
+            "{} needs an extra check. Enter the verification code {challenge} along with your one-time password",
+            txn.describe()
+        );
+        self.notify(account, Notice::TxnChallenge, body);
+    }
+}
@@ -0,0 +1,104 @@
+use crate::{
+    check_ok_500,
+    database::repository::Repository,
+    find_one, insert_one,
+    models::{handlers::Response, helpers::common::timestamp_millis, idempotency::IDEMPOTENCY},
+    update_one,
+};
+use mongodb::bson::{doc, to_bson, Bson};
+use rocket::http::Status;
+
+impl Repository {
+    // Reserves the key, or returns the stored outcome of the first request
+    // Throws 400 when the key was used on another route, 409 while the first request runs
+    // and 422 when the body differs from the first request, an abandoned key is taken over
+    pub async fn begin_idempotent(
+        &self,
+        key: &str,
+        sub: &str,
+        route: &str,
+        fingerprint: String,
+    ) -> Result<Option<Result<Response<String>, Status>>, Status> {
+        let record = IDEMPOTENCY::new(key, sub, route, fingerprint.to_owned());
+        match insert_one!(&self.idempotency, record, None) {
+            Ok(_) => return Ok(None),
+            Err(error) if error != Status::Conflict => return Err(error),
+            Err(_) => (),
+        };
+
+        let record: IDEMPOTENCY = find_one!(&self.idempotency, None, ("key", key), ("sub", sub))?;
+        if record.route != route {
+            return Err(Status::BadRequest);
+        }
+        if !record.matches(&fingerprint) {
+            return Err(Status::UnprocessableEntity);
+        }
+        if let Some(result) = record.result() {
+            return Ok(Some(result));
+        }
+        if !record.is_abandoned() {
+            return Err(Status::Conflict);
+        }
+
+        // Only one retry wins the key, the others keep getting 409
+        let started = match record.started {
+            0 => Bson::Document(doc! { "$exists": false }),
+            started => Bson::Int64(started),
+        };
+        let query = doc! {
+            "key": key,
+            "sub": sub,
+            "started": started,
+            "response": { "$exists": false },
+            "status": { "$exists": false }
+        };
+        let update = doc! {
+            "$set": {
+                "started": timestamp_millis()
+            }
+        };
+        let result = update_one!(&self.idempotency, update, None, query)?;
+        match result.modified_count {
+            1 => Ok(None),
+            _ => Err(Status::Conflict),
+        }
+    }
+
+    // Server failures free the key again so the request can be retried with it
+    pub async fn finish_idempotent(
+        &self,
+        key: &str,
+        sub: &str,
+        result: &Result<Response<String>, Status>,
+    ) -> Result<(), Status> {
+        if let Err(status) = result {
+            if status.code >= 500 {
+                check_ok_500!(
+                    self.idempotency
+                        .delete_one(doc! { "key": key, "sub": sub }, None),
+                    "Releasing Idempotency Key"
+                )?;
+                return Ok(());
+            }
+        }
+        let update = match result {
+            Ok(response) => match to_bson(response) {
+                Ok(response) => doc! {
+                    "$set": {
+                        "response": response
+                    }
+                },
+                Err(_) => return Err(Status::InternalServerError),
+            },
+            Err(status) => doc! {
+                "$set": {
+                    "status": status.code as i32
+                }
+            },
+        };
+        match update_one!(&self.idempotency, update, None, ("key", key), ("sub", sub)) {
+            Ok(_) => Ok(()),
+            Err(error) => Err(error),
+        }
+    }
+}
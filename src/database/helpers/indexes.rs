@@ -2,7 +2,7 @@ use std::time::Duration;
 
 use mongodb::{bson::doc, options::IndexOptions, IndexModel};
 
-use crate::models::token::TOKEN;
+use crate::models::{idempotency::IDEMPOTENCY, token::TOKEN};
 
 pub fn admin_indexes() -> IndexModel {
     let options = IndexOptions::builder().unique(true).build();
@@ -24,6 +24,14 @@ pub fn atm_indexes() -> IndexModel {
         .build()
 }
 
+pub fn atm_geo_indexes() -> IndexModel {
+    IndexModel::builder()
+        .keys(doc! {
+            "coordinates": "2dsphere",
+        })
+        .build()
+}
+
 pub fn account_indexes() -> IndexModel {
     let options = IndexOptions::builder().unique(true).build();
     IndexModel::builder()
@@ -34,6 +42,51 @@ pub fn account_indexes() -> IndexModel {
         .build()
 }
 
+pub fn txn_account_indexes() -> IndexModel {
+    IndexModel::builder()
+        .keys(doc! {
+            "account": 1,
+            "_id": -1,
+        })
+        .build()
+}
+
+pub fn txn_destination_indexes() -> IndexModel {
+    IndexModel::builder()
+        .keys(doc! {
+            "destination": 1,
+            "_id": -1,
+        })
+        .build()
+}
+
+pub fn txn_atm_indexes() -> IndexModel {
+    IndexModel::builder()
+        .keys(doc! {
+            "atm": 1,
+            "_id": -1,
+        })
+        .build()
+}
+
+pub fn ledger_indexes() -> IndexModel {
+    IndexModel::builder()
+        .keys(doc! {
+            "book": 1,
+            "name": 1,
+        })
+        .build()
+}
+
+pub fn delivery_indexes() -> IndexModel {
+    IndexModel::builder()
+        .keys(doc! {
+            "status": 1,
+            "next_attempt": 1,
+        })
+        .build()
+}
+
 pub fn token_indexes() -> IndexModel {
     let duration = Duration::from_millis(TOKEN::DEFAULT_EXPIRY as u64);
     let options = IndexOptions::builder().expire_after(duration).build();
@@ -44,3 +97,56 @@ pub fn token_indexes() -> IndexModel {
         .options(options)
         .build()
 }
+
+pub fn refresh_indexes() -> IndexModel {
+    let options = IndexOptions::builder().unique(true).build();
+    IndexModel::builder()
+        .keys(doc! {
+            "hash": 1,
+        })
+        .options(options)
+        .build()
+}
+
+pub fn refresh_sub_indexes() -> IndexModel {
+    IndexModel::builder()
+        .keys(doc! {
+            "sub": 1,
+        })
+        .build()
+}
+
+// Every refresh token is removed once its login runs out
+pub fn refresh_expiry_indexes() -> IndexModel {
+    let options = IndexOptions::builder()
+        .expire_after(Duration::from_secs(0))
+        .build();
+    IndexModel::builder()
+        .keys(doc! {
+            "expires": 1,
+        })
+        .options(options)
+        .build()
+}
+
+pub fn idempotency_indexes() -> IndexModel {
+    let options = IndexOptions::builder().unique(true).build();
+    IndexModel::builder()
+        .keys(doc! {
+            "key": 1,
+            "sub": 1,
+        })
+        .options(options)
+        .build()
+}
+
+pub fn idempotency_expiry_indexes() -> IndexModel {
+    let duration = Duration::from_millis(IDEMPOTENCY::DEFAULT_EXPIRY as u64);
+    let options = IndexOptions::builder().expire_after(duration).build();
+    IndexModel::builder()
+        .keys(doc! {
+            "created": 1,
+        })
+        .options(options)
+        .build()
+}
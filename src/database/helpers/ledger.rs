@@ -0,0 +1,158 @@
+use crate::{
+    check_ok_500,
+    database::repository::Repository,
+    find_many, find_one,
+    models::{
+        ledger::{Book, Side, DISCREPANCY, ENTRY},
+        transaction::TRANSACTION,
+        user::ACCOUNT,
+    },
+};
+use mongodb::{
+    bson::{doc, oid::ObjectId, Bson},
+    options::FindOneOptions,
+    sync::ClientSession,
+};
+use rocket::http::Status;
+use std::collections::HashMap;
+
+impl Repository {
+    // Writes the balanced entries of a transaction inside the caller's session
+    pub fn post_entries_with_session(
+        &self,
+        session: &mut ClientSession,
+        id: ObjectId,
+        txn: &TRANSACTION,
+    ) -> Result<(), Status> {
+        let entries = match ENTRY::for_txn(id, txn) {
+            Ok(entries) => entries,
+            Err(error) => {
+                println!("Ledger Error: {error}");
+                return Err(Status::InternalServerError);
+            }
+        };
+        match self.ledger.insert_many_with_session(entries, None, session) {
+            Ok(_) => Ok(()),
+            Err(_) => Err(Status::InternalServerError),
+        }
+    }
+
+    // Customer balances as recorded by the ledger, credits minus debits
+    pub async fn get_ledger_balances(&self) -> Result<HashMap<String, i64>, Status> {
+        let pipeline = vec![
+            doc! {
+                "$match": {
+                    "book": Book::ACCOUNT.value()
+                }
+            },
+            doc! {
+                "$group": {
+                    "_id": "$name",
+                    "balance": {
+                        "$sum": {
+                            "$cond": [
+                                { "$eq": ["$side", Side::CREDIT.value()] },
+                                "$amount",
+                                { "$multiply": ["$amount", -1] }
+                            ]
+                        }
+                    }
+                }
+            },
+        ];
+
+        let cursor = check_ok_500!(self.ledger.aggregate(pipeline, None))?;
+        let mut balances = HashMap::new();
+        for result in cursor {
+            let result = check_ok_500!(result)?;
+            let name = match result.get_str("_id") {
+                Ok(name) => name.to_string(),
+                Err(_) => continue,
+            };
+            let balance = match result.get("balance") {
+                Some(Bson::Int64(balance)) => *balance,
+                Some(Bson::Int32(balance)) => *balance as i64,
+                _ => 0,
+            };
+            balances.insert(name, balance);
+        }
+        Ok(balances)
+    }
+
+    // Runs at start up, every account that predates the ledger is opened once with the
+    // difference between its stored balance and its entries, an opening of 0 is posted
+    // as well so a later difference is never booked away
+    pub async fn post_opening_balances(&self) -> Result<usize, Status> {
+        let options = FindOneOptions::builder()
+            .sort(doc! { "created": 1 })
+            .build();
+        let filter = doc! {};
+        let first: Result<ENTRY, Status> = find_one!(&self.ledger, options, filter);
+        // Accounts created once the ledger was kept start at 0 and are never opened
+        let cutover = match first {
+            Ok(entry) => Some(entry.created),
+            Err(error) if error == Status::NotFound => None,
+            Err(error) => return Err(error),
+        };
+
+        // Opening entries carry the id of the account they opened
+        let filter = doc! { "book": Book::OPENING.value() };
+        let opened = check_ok_500!(
+            self.ledger.distinct("txn", filter, None),
+            "Finding Opened Accounts"
+        )?;
+
+        let balances = self.get_ledger_balances().await?;
+        let filter = doc! {};
+        let accounts: Vec<ACCOUNT> = find_many!(&self.account, None, filter)?;
+        let mut entries = Vec::new();
+        for account in accounts {
+            let (id, number) = match (account.id, account.number) {
+                (Some(id), Some(number)) => (id, number),
+                _ => continue,
+            };
+            if opened.contains(&Bson::ObjectId(id)) {
+                continue;
+            }
+            if cutover.map_or(false, |cutover| id.timestamp() >= cutover) {
+                continue;
+            }
+            let ledger = balances.get(&number).copied().unwrap_or(0);
+            entries.extend(ENTRY::for_opening(id, &number, account.balance - ledger));
+        }
+
+        let count = entries.len() / 2;
+        if count > 0 {
+            check_ok_500!(
+                self.ledger.insert_many(entries, None),
+                "Posting Opening Balances"
+            )?;
+        }
+        Ok(count)
+    }
+
+    // Recomputes every balance from the ledger and reports the accounts that differ
+    pub async fn reconcile_ledger(&self) -> Result<Vec<DISCREPANCY>, Status> {
+        let balances = self.get_ledger_balances().await?;
+        let filter = doc! {};
+        let accounts: Vec<ACCOUNT> = find_many!(&self.account, None, filter)?;
+
+        let discrepancies = accounts
+            .into_iter()
+            .filter_map(|account| {
+                let number = account.number?;
+                let ledger = balances.get(&number).copied().unwrap_or(0);
+                match account.balance == ledger {
+                    true => None,
+                    false => Some(DISCREPANCY {
+                        number,
+                        stored: account.balance,
+                        ledger,
+                        difference: account.balance - ledger,
+                    }),
+                }
+            })
+            .collect();
+        Ok(discrepancies)
+    }
+}
@@ -0,0 +1,26 @@
+use crate::{
+    check_result, database::repository::Repository, insert_one,
+    models::helpers::common::timestamp_millis, models::logs::LOG,
+};
+use mongodb::bson::{oid::ObjectId, Bson};
+
+impl Repository {
+    // Records the old and new value of a changed field, logging failures are only printed
+    pub async fn log_update(
+        &self,
+        creator: ObjectId,
+        role: &str,
+        affected_id: ObjectId,
+        field: &str,
+        old: Bson,
+        new: Bson,
+    ) {
+        let log = LOG::new()
+            .creator(creator)
+            .timestamp(timestamp_millis())
+            .role(role)
+            .update(affected_id, field, old, new)
+            .build();
+        check_result!(insert_one!(&self.logs, log, None), "Logging");
+    }
+}
@@ -40,6 +40,31 @@ macro_rules! find_one {
     }};
 }
 
+#[macro_export]
+macro_rules! find_many {
+    ($collection:expr, $options:ident, $(($key:expr, $value:expr)),*) => {{
+        let mut filter = mongodb::bson::Document::new();
+        $(filter.insert($key.to_owned(), $value.to_owned());)*
+        match $collection.find(filter, $options) {
+            Ok(cursor) => match cursor.collect::<Result<Vec<_>, _>>() {
+                Ok(result) => Ok(result),
+                Err(_) => Err(rocket::http::Status::InternalServerError),
+            },
+            Err(_) => Err(rocket::http::Status::InternalServerError),
+        }
+    }};
+
+    ($collection:expr, $options:ident, $filter:ident) => {{
+        match $collection.find($filter, $options) {
+            Ok(cursor) => match cursor.collect::<Result<Vec<_>, _>>() {
+                Ok(result) => Ok(result),
+                Err(_) => Err(rocket::http::Status::InternalServerError),
+            },
+            Err(_) => Err(rocket::http::Status::InternalServerError),
+        }
+    }};
+}
+
 #[macro_export]
 macro_rules! find_one_and_update {
     ($collection:expr, $options:ident, $update:ident, $(($key:expr, $value:expr)),*) => {{
@@ -86,6 +111,26 @@ macro_rules! update_one {
     }};
 }
 
+#[macro_export]
+macro_rules! update_one_with_session {
+    ($collection:expr, $update:ident, $options:ident, $session:expr, $(($key:expr, $value:expr)),*) => {{
+        let mut query = mongodb::bson::Document::new();
+        $(query.insert($key.to_owned(), $value.to_owned());)*
+
+        match $collection.update_one_with_session(query, $update, $options, $session) {
+            Ok(result) => Ok(result),
+            Err(_) => Err(rocket::http::Status::InternalServerError),
+        }
+    }};
+
+    ($collection:expr, $update:ident, $options:ident, $session:expr, $query:ident) => {{
+        match $collection.update_one_with_session($query, $update, $options, $session) {
+            Ok(result) => Ok(result),
+            Err(_) => Err(rocket::http::Status::InternalServerError),
+        }
+    }};
+}
+
 #[macro_export]
 macro_rules! update_many {
     ($collection:expr, $update:ident, $options:ident, $(($key:expr, $value:expr)),*) => {{
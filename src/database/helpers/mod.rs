@@ -1,7 +1,14 @@
 pub mod admin;
 pub mod atm;
+pub mod events;
+pub mod fraud;
+pub mod idempotency;
 pub mod indexes;
+pub mod ledger;
+pub mod logs;
 pub mod macros;
+pub mod notification;
 pub mod token;
 pub mod transaction;
 pub mod user;
+pub mod webhook;
@@ -0,0 +1,103 @@
+use crate::{
+    database::repository::Repository,
+    models::{
+        notification::{Notice, NOTIFICATION},
+        transaction::{TxnStatus, TxnType, TRANSACTION},
+        user::ACCOUNT,
+    },
+    option,
+};
+use rocket::tokio::runtime::Handle;
+
+impl Repository {
+    // Nothing is sent to accounts without contact details
+    pub fn notify(&self, account: &ACCOUNT, notice: Notice, body: String) {
+        let contact = option!(val -> &account.contact; {val} | {return});
+        let notifications = contact
+            .recipients()
+            .into_iter()
+            .map(|(channel, recipient)| {
+                NOTIFICATION::new(channel, recipient, notice, body.to_owned())
+            })
+            .collect();
+        self.notifier.dispatch(notifications);
+    }
+
+    // Looks the account up in the background, a creation also carries the current one-time password
+    pub fn notify_txn(&self, txn: &TRANSACTION, status: TxnStatus) {
+        self.notify_destination(txn, status);
+        let notice = option!(val -> Notice::from_status(status); {val} | {return});
+        let number = option!(val -> txn.account.clone(); {val} | {return});
+        let handle = option!(val -> Handle::try_current().ok(); {val} | {return});
+        let (repository, txn) = (self.clone(), txn.clone());
+
+        handle.spawn(async move {
+            let account = match repository.get_account(number).await {
+                Ok(account) => account,
+                Err(error) => {
+                    println!("Notification Error: {error}");
+                    return;
+                }
+            };
+            if account.contact.is_none() {
+                return;
+            }
+            let summary = txn.describe();
+            let body = match notice {
+                Notice::TxnCreated => match repository.get_current_otp(&account).await {
+                    Ok(otp) => format!(
+                        "{summary} is waiting for confirmation. Your one-time password is {otp}, never share it"
+                    ),
+                    Err(error) => {
+                        println!("Notification Error: {error}");
+                        return;
+                    }
+                },
+                Notice::TxnCompleted => format!("{summary} completed"),
+                Notice::TxnRejected => {
+                    format!("{summary} was rejected. If this wasn't you, contact your bank")
+                }
+                Notice::TxnChallenge
+                | Notice::NewDevice
+                | Notice::TxnReceived
+                | Notice::TxnReversed => return,
+            };
+            repository.notify(&account, notice, body);
+        });
+    }
+
+    // The receiving account of a transfer hears when the money arrives and when it is taken back
+    fn notify_destination(&self, txn: &TRANSACTION, status: TxnStatus) {
+        if txn.txn_type != TxnType::TRANSFER {
+            return;
+        }
+        let notice = match status {
+            TxnStatus::COMPLETE => Notice::TxnReceived,
+            TxnStatus::REVERSED => Notice::TxnReversed,
+            _ => return,
+        };
+        let number = option!(val -> txn.destination.clone(); {val} | {return});
+        let source = txn.account.clone().unwrap_or_default();
+        let handle = option!(val -> Handle::try_current().ok(); {val} | {return});
+        let (repository, amount) = (self.clone(), txn.amount);
+
+        handle.spawn(async move {
+            let account = match repository.get_account(number).await {
+                Ok(account) => account,
+                Err(error) => {
+                    println!("Notification Error: {error}");
+                    return;
+                }
+            };
+            let body = match notice {
+                Notice::TxnReceived => {
+                    format!("Transfer of {amount} from account {source} received")
+                }
+                _ => format!(
+                    "Transfer of {amount} from account {source} was reversed and taken back"
+                ),
+            };
+            repository.notify(&account, notice, body);
+        });
+    }
+}
@@ -1,10 +1,15 @@
 use crate::{
+    check_ok_500,
     database::repository::Repository,
-    find_one, insert_one, log_action,
-    models::{helpers::common::timestamp, token::JWT},
+    find_one, find_one_and_update, insert_one, log_action,
+    models::{
+        helpers::common::{timestamp, timestamp_millis},
+        token::{Type, JWT, REFRESH},
+    },
     option,
+    utilities::crypto::{hasher, to_hex, Generator},
 };
-use mongodb::bson::oid::ObjectId;
+use mongodb::bson::{doc, oid::ObjectId, Bson};
 use rocket::http::Status;
 
 impl Repository {
@@ -19,4 +24,110 @@ impl Repository {
         log_action!(&self.logs, creator, timestamp(), "TOKEN", id);
         Ok(id)
     }
+
+    // Throws 500, a fresh login starts a new family and drops the earlier ones of the principal
+    pub async fn issue_refresh_token(
+        &self,
+        sub: ObjectId,
+        role: Type,
+        previous: Option<&REFRESH>,
+    ) -> Result<String, Status> {
+        let (family, expires) = match previous {
+            Some(previous) => (previous.family, previous.expires.timestamp_millis()),
+            None => {
+                self.revoke_refresh_tokens(sub).await?;
+                let expires = timestamp_millis() + self.settings.refresh_token_expiry;
+                (ObjectId::new(), expires)
+            }
+        };
+        let token = to_hex(&Generator::generate_random_bytes());
+        let refresh = REFRESH::new(hasher(token.to_owned()), family, sub, role, expires);
+        insert_one!(&self.refresh, refresh, None)?;
+        Ok(token)
+    }
+
+    // Throws 401 and 500, a token can only be traded once
+    pub async fn rotate_refresh_token(&self, token: &str) -> Result<REFRESH, Status> {
+        let hash = hasher(token.to_string());
+        let update = doc! {
+            "$set": {
+                "used": true
+            }
+        };
+        let options = None;
+        let result: Result<REFRESH, Status> = find_one_and_update!(
+            &self.refresh,
+            options,
+            update,
+            ("hash", &hash),
+            ("used", false)
+        );
+        match result {
+            Ok(refresh) => match refresh.expires.timestamp_millis() > timestamp_millis() {
+                true => Ok(refresh),
+                false => Err(Status::Unauthorized),
+            },
+            Err(error) if error == Status::NotFound => {
+                // A used token coming back means it was copied, so nobody keeps the session
+                let reused: Result<REFRESH, Status> =
+                    find_one!(&self.refresh, None, ("hash", &hash));
+                if let Ok(refresh) = reused {
+                    println!(
+                        "Refresh token reused for {}, ending the session",
+                        refresh.sub
+                    );
+                    self.revoke_session(&refresh.sub.to_string(), refresh.role)
+                        .await?;
+                }
+                Err(Status::Unauthorized)
+            }
+            Err(error) => Err(error),
+        }
+    }
+
+    // False once the token was revoked, replaced by another login or ran out
+    pub async fn token_alive(&self, id: ObjectId) -> bool {
+        match self.get_token(&id.to_hex()).await {
+            Ok(jwt) => match jwt.token_from_jwt(&self.keys) {
+                Ok(token) => token.exp > timestamp_millis(),
+                Err(_) => false,
+            },
+            Err(_) => false,
+        }
+    }
+
+    // Throws 500
+    pub async fn revoke_refresh_tokens(&self, sub: ObjectId) -> Result<(), Status> {
+        check_ok_500!(
+            self.refresh.delete_many(doc! { "sub": sub }, None),
+            "Revoking Refresh Tokens"
+        )?;
+        Ok(())
+    }
+
+    // Throws 404 and 500, signs the principal out everywhere
+    pub async fn revoke_session(&self, sub: &str, role: Type) -> Result<(), Status> {
+        let id = match role {
+            Type::ADMIN => {
+                let admin = self.get_admin_from_id(sub).await?;
+                self.revoke_admin_token(&admin).await?;
+                admin.id
+            }
+            Type::ATM => {
+                let atm = self.get_atm_from_id(sub).await?;
+                self.revoke_atm_token(&atm).await?;
+                atm.id
+            }
+            Type::ACCOUNT => {
+                let account = self.get_account_from_id(sub).await?;
+                self.revoke_account_token(&account).await?;
+                account.id
+            }
+        };
+        let id = option!(val -> id; {val} | {return Err(Status::NotFound)});
+        let old = Bson::String("$SEALED$".to_string());
+        self.log_update(id, role.log_role(), id, "token", old, Bson::Null)
+            .await;
+        Ok(())
+    }
 }
@@ -1,17 +1,20 @@
 #![allow(dead_code)]
-use std::str::FromStr;
-
 use crate::{
-    find_one, generate_one,
+    check_ok_500, check_result, find_many, find_one, find_one_and_update, generate_one, insert_one,
     models::{
+        events::TxnEvent,
         helpers::common::timestamp_millis,
-        transaction::{TxnStatus, TRANSACTION},
+        logs::LOG,
+        transaction::{TxnLimit, TxnStatus, TxnType, TRANSACTION, TRANSITION},
+        user::AccountStatus,
     },
-    update_many, update_one,
+    option, update_many, update_one, update_one_with_session,
 };
 use mongodb::{
-    bson::{doc, oid::ObjectId},
-    options::FindOneOptions,
+    bson::{doc, oid::ObjectId, to_bson},
+    bson::{Bson, DateTime, Document},
+    options::{FindOneAndUpdateOptions, FindOneOptions, FindOptions, ReturnDocument},
+    sync::ClientSession,
 };
 use rocket::http::Status;
 
@@ -19,14 +22,31 @@ use crate::database::repository::Repository;
 
 impl Repository {
     // Throws 400, 409 and 500
-    pub async fn create_txn(&self, id: Option<ObjectId>, data: TRANSACTION) -> Result<(), Status> {
+    pub async fn create_txn(
+        &self,
+        id: Option<ObjectId>,
+        role: &str,
+        mut data: TRANSACTION,
+    ) -> Result<(), Status> {
         let id = match id {
             Some(result) => result,
             None => return Err(Status::BadRequest),
         };
+        let transition = TRANSITION::new(None, data.status, Some(id), role);
+        data.transitions.push(transition);
+        data.id = data.id.or_else(|| Some(ObjectId::new())); // Screening may have set it
+        let event = TxnEvent::new(&data, data.status);
+        let txn = data.clone();
         let logs = &self.logs;
         let timestamp = timestamp_millis();
-        generate_one!(&self.txn, logs, data, None, id, timestamp, "TRANSACTION")
+        generate_one!(&self.txn, logs, data, None, id, timestamp, "TRANSACTION")?;
+        self.publish(event);
+        self.notify_txn(&txn, txn.status);
+        Ok(())
+    }
+
+    pub async fn get_txn_from_id(&self, id: &str) -> Result<TRANSACTION, Status> {
+        find_one!(&self.txn, None, _id => id)
     }
 
     pub async fn get_recent_txn(&self, field: &str, value: &str) -> Result<TRANSACTION, Status> {
@@ -42,40 +62,522 @@ impl Repository {
         find_one!(&self.txn, None, (field, value), ("status", "PENDING"))
     }
 
-    pub async fn reject_all_pending_txn(&self, field: &str, value: &str) -> Result<(), Status> {
-        let update = doc! {
-            "$set": {
-                "status": "REJECTED"
+    // Newest first, fetches one extra document to tell if another page exists
+    pub async fn get_txn_history(
+        &self,
+        filter: Document,
+        limit: i64,
+    ) -> Result<(Vec<TRANSACTION>, Option<ObjectId>), Status> {
+        let options = FindOptions::builder()
+            .sort(doc! {
+                "_id": -1
+            })
+            .limit(limit + 1)
+            .build();
+        let mut txns: Vec<TRANSACTION> = find_many!(&self.txn, options, filter)?;
+
+        let next = match txns.len() as i64 > limit {
+            true => {
+                txns.truncate(limit as usize);
+                txns.last().and_then(|txn| txn.id)
             }
+            false => None,
         };
+        Ok((txns, next))
+    }
+
+    // Sum of COMPLETE withdrawals matching the field since the business day started
+    pub async fn get_daily_withdrawn(&self, field: &str, value: &str) -> Result<i64, Status> {
+        let since = DateTime::from_millis(self.settings.business_day_start());
+        let pipeline = vec![
+            doc! {
+                "$match": {
+                    field: value,
+                    "txn_type": TxnType::DEBIT.to_string(),
+                    "status": TxnStatus::COMPLETE.to_string(),
+                    "created": { "$gte": since }
+                }
+            },
+            doc! {
+                "$group": {
+                    "_id": Bson::Null,
+                    "total": { "$sum": "$amount" }
+                }
+            },
+        ];
+
+        let mut cursor = check_ok_500!(self.txn.aggregate(pipeline, None))?;
+        match cursor.next() {
+            Some(Ok(result)) => match result.get("total") {
+                Some(Bson::Int64(total)) => Ok(*total),
+                Some(Bson::Int32(total)) => Ok(*total as i64),
+                _ => Ok(0),
+            },
+            Some(Err(_)) => Err(Status::InternalServerError),
+            None => Ok(0),
+        }
+    }
+
+    // Returns the first withdrawal limit the amount would exceed
+    pub async fn check_txn_limits(
+        &self,
+        number: &str,
+        atm: &str,
+        amount: i64,
+    ) -> Result<Option<TxnLimit>, Status> {
+        let settings = &self.settings;
+        if settings.txn_limit > 0 && amount > settings.txn_limit {
+            return Ok(Some(TxnLimit::TRANSACTION));
+        }
+        if settings.daily_limit > 0 {
+            let withdrawn = self.get_daily_withdrawn("account", number).await?;
+            if withdrawn + amount > settings.daily_limit {
+                return Ok(Some(TxnLimit::DAILY));
+            }
+        }
+        if settings.atm_daily_limit > 0 {
+            let withdrawn = self.get_daily_withdrawn("atm", atm).await?;
+            if withdrawn + amount > settings.atm_daily_limit {
+                return Ok(Some(TxnLimit::ATM));
+            }
+        }
+        Ok(None)
+    }
+
+    // Builds the update for every status write, throws 409 for a transition the state machine forbids
+    fn transition_update(
+        from: TxnStatus,
+        to: TxnStatus,
+        actor: Option<ObjectId>,
+        role: &str,
+        mut fields: Document,
+    ) -> Result<Document, Status> {
+        if !from.can_transition(to) {
+            return Err(Status::Conflict);
+        }
+        let transition = TRANSITION::new(Some(from), to, actor, role);
+        let transition = check_ok_500!(to_bson(&transition), "Serializing Transition")?;
+        fields.insert("status", to.to_string());
+        Ok(doc! {
+            "$set": fields,
+            "$push": {
+                "transitions": transition
+            }
+        })
+    }
+
+    // Matches the transaction only while it is still in the from status, so a concurrent write loses
+    fn transition_filter(id: ObjectId, from: TxnStatus) -> Document {
+        doc! {
+            "_id": id,
+            "status": from.to_string()
+        }
+    }
+
+    // Throws 404 when the transaction is missing and 409 when it is no longer in the from status
+    pub async fn transition_txn(
+        &self,
+        id: ObjectId,
+        from: TxnStatus,
+        to: TxnStatus,
+        actor: Option<ObjectId>,
+        role: &str,
+    ) -> Result<(), Status> {
+        let update = Self::transition_update(from, to, actor, role, Document::new())?;
+        let filter = Self::transition_filter(id, from);
+
+        let mut session = check_ok_500!(self.client.start_session(None), "Starting Session")?;
+        check_ok_500!(session.start_transaction(None), "Starting Transition")?;
+
+        let result =
+            match self
+                .txn
+                .find_one_and_update_with_session(filter, update, None, &mut session)
+            {
+                Ok(Some(txn)) => self
+                    .enqueue_webhooks_with_session(&mut session, &txn, to)
+                    .map(|_| txn),
+                Ok(None) => Err(Status::NotFound),
+                Err(_) => Err(Status::InternalServerError),
+            };
+        match result {
+            Ok(txn) => {
+                check_ok_500!(session.commit_transaction(), "Committing Transition")?;
+                self.announce_txn_event(&txn, to);
+                Ok(())
+            }
+            Err(error) => {
+                check_result!(session.abort_transaction(), "Aborting Transition");
+                if error != Status::NotFound {
+                    return Err(error);
+                }
+                match self.get_txn_from_id(&id.to_hex()).await {
+                    Ok(_) => Err(Status::Conflict),
+                    Err(error) => Err(error),
+                }
+            }
+        }
+    }
+
+    pub async fn reject_all_pending_txn(
+        &self,
+        field: &str,
+        value: &str,
+        actor: Option<ObjectId>,
+        role: &str,
+    ) -> Result<(), Status> {
+        let (from, to) = (TxnStatus::PENDING, TxnStatus::REJECTED);
+        let pending: Vec<TRANSACTION> = find_many!(
+            &self.txn,
+            None,
+            (field, value),
+            ("status", from.to_string())
+        )?;
+        let update = Self::transition_update(from, to, actor, role, Document::new())?;
         match update_many!(
             &self.txn,
             update,
             None,
             (field, value),
-            ("status", "PENDING")
+            ("status", from.to_string())
         ) {
-            Ok(_) => Ok(()),
+            Ok(_) => {
+                pending
+                    .iter()
+                    .for_each(|txn| self.publish_txn_event(txn, to));
+                Ok(())
+            }
             Err(error) => Err(error),
         }
     }
 
-    pub async fn confirm_txn(&self, field: &str, value: &str, status: &str) -> Result<(), Status> {
-        let status = TxnStatus::from_str(status).unwrap().to_string();
+    // Marks every PENDING transaction past DEFAULT_EXPIRY as EXPIRED, returns the count
+    pub async fn expire_stale_txn(&self) -> Result<u64, Status> {
+        let cutoff = DateTime::from_millis(timestamp_millis() - TRANSACTION::DEFAULT_EXPIRY);
+        let filter = doc! {
+            "status": TxnStatus::PENDING.to_string(),
+            "created": { "$lt": cutoff }
+        };
+        let stale: Vec<TRANSACTION> = find_many!(&self.txn, None, filter)?;
+
+        let mut expired = 0;
+        for txn in stale.iter() {
+            let id = option!(val -> txn.id; {val} | {continue});
+            let (from, to) = (TxnStatus::PENDING, TxnStatus::EXPIRED);
+            let update = Self::transition_update(from, to, None, "System", Document::new())?;
+            let filter = Self::transition_filter(id, from);
+            let result = update_one!(&self.txn, update, None, filter)?;
+            if result.modified_count == 1 {
+                expired += 1;
+                self.publish_txn_event(txn, to);
+                let log = LOG::new()
+                    .timestamp(timestamp_millis())
+                    .role("TRANSACTION")
+                    .expiration(id)
+                    .build();
+                check_result!(insert_one!(&self.logs, log, None), "Logging");
+            }
+        }
+        Ok(expired)
+    }
+
+    // Returns true when this failure locked the transaction
+    pub async fn record_failed_attempt(
+        &self,
+        txn: &TRANSACTION,
+        actor: Option<ObjectId>,
+        role: &str,
+    ) -> Result<bool, Status> {
+        let id = option!(val -> txn.id; {val} | {return Err(Status::NotFound)});
+        let number = option!(val -> txn.account.as_ref(); {val} | {return Err(Status::NotFound)});
+
+        let options = FindOneAndUpdateOptions::builder()
+            .return_document(ReturnDocument::After)
+            .build();
         let update = doc! {
-            "$set": {
-                "status": status
+            "$inc": {
+                "attempts": 1
             }
         };
-        match update_one!(
+        let txn: TRANSACTION = find_one_and_update!(
             &self.txn,
+            options,
             update,
-            None,
-            (field, value),
-            ("status", "PENDING")
-        ) {
-            Ok(_) => Ok(()),
-            Err(error) => Err(error),
+            ("_id", id),
+            ("status", TxnStatus::PENDING.to_string())
+        )?;
+        if txn.attempts < self.settings.max_otp_attempts {
+            return Ok(false);
+        }
+
+        let (from, to) = (TxnStatus::PENDING, TxnStatus::REJECTED);
+        let update = Self::transition_update(from, to, actor, role, Document::new())?;
+        let filter = Self::transition_filter(id, from);
+        let result = update_one!(&self.txn, update, None, filter)?;
+        if result.modified_count != 1 {
+            return Ok(false);
         }
+        self.publish_txn_event(&txn, to);
+
+        let log = LOG::new()
+            .timestamp(timestamp_millis())
+            .role("TRANSACTION")
+            .lockout(id, number.to_owned())
+            .build();
+        check_result!(insert_one!(&self.logs, log, None), "Logging");
+        check_result!(self.record_lockout(number).await, "Recording Lockout");
+        Ok(true)
+    }
+
+    // Throws 403, 404, 406, 409 and 500
+    pub async fn settle_txn(
+        &self,
+        txn: &TRANSACTION,
+        actor: Option<ObjectId>,
+        role: &str,
+    ) -> Result<(), Status> {
+        let id = option!(val -> txn.id; {val} | {return Err(Status::NotFound)});
+        let number = option!(val -> txn.account.as_ref(); {val} | {return Err(Status::NotFound)});
+        // A negative amount would turn a debit into a credit and the other way around
+        if txn.amount <= 0 {
+            return Err(Status::NotAcceptable);
+        }
+
+        let mut session = check_ok_500!(self.client.start_session(None), "Starting Session")?;
+        check_ok_500!(session.start_transaction(None), "Starting Settlement")?;
+
+        let result = self
+            .apply_settlement(&mut session, id, number, txn, actor, role)
+            .and_then(|_| {
+                self.enqueue_webhooks_with_session(&mut session, txn, TxnStatus::COMPLETE)
+            });
+        match result {
+            Ok(_) => {
+                check_ok_500!(session.commit_transaction(), "Committing Settlement")?;
+                self.announce_txn_event(txn, TxnStatus::COMPLETE);
+                Ok(())
+            }
+            Err(error) => {
+                check_result!(session.abort_transaction(), "Aborting Settlement");
+                Err(error)
+            }
+        }
+    }
+
+    // Throws 403, 404, 406, 409 and 500, returns the id of the compensating transaction
+    pub async fn reverse_txn(
+        &self,
+        creator: ObjectId,
+        txn: &TRANSACTION,
+        reason: String,
+    ) -> Result<ObjectId, Status> {
+        let id = option!(val -> txn.id; {val} | {return Err(Status::NotFound)});
+        let reversal = txn.reversal();
+
+        let mut session = check_ok_500!(self.client.start_session(None), "Starting Session")?;
+        check_ok_500!(session.start_transaction(None), "Starting Reversal")?;
+
+        let result = self
+            .apply_reversal(&mut session, creator, id, reversal)
+            .and_then(|reversal_id| {
+                self.enqueue_webhooks_with_session(&mut session, txn, TxnStatus::REVERSED)
+                    .map(|_| reversal_id)
+            });
+        let reversal_id = match result {
+            Ok(reversal_id) => {
+                check_ok_500!(session.commit_transaction(), "Committing Reversal")?;
+                self.announce_txn_event(txn, TxnStatus::REVERSED);
+                reversal_id
+            }
+            Err(error) => {
+                check_result!(session.abort_transaction(), "Aborting Reversal");
+                return Err(error);
+            }
+        };
+
+        let log = LOG::new()
+            .creator(creator)
+            .timestamp(timestamp_millis())
+            .role("TRANSACTION")
+            .reversal(id, reversal_id, reason)
+            .build();
+        check_result!(insert_one!(&self.logs, log, None), "Logging");
+        Ok(reversal_id)
+    }
+
+    fn apply_reversal(
+        &self,
+        session: &mut ClientSession,
+        creator: ObjectId,
+        id: ObjectId,
+        mut reversal: TRANSACTION,
+    ) -> Result<ObjectId, Status> {
+        let transition = TRANSITION::new(None, reversal.status, Some(creator), "Admin");
+        reversal.transitions.push(transition);
+        let number =
+            option!(val -> reversal.account.clone(); {val} | {return Err(Status::NotFound)});
+        let (txn_type, amount) = (reversal.txn_type, reversal.amount);
+        let destination = reversal.destination.clone();
+
+        let reversal_id = match self.txn.insert_one_with_session(&reversal, None, session) {
+            Ok(result) => option!(val -> result.inserted_id.as_object_id(); {val} | {
+                return Err(Status::InternalServerError)
+            }),
+            Err(_) => return Err(Status::InternalServerError),
+        };
+
+        // Only a COMPLETE transaction can be reversed, and only once
+        let (from, to) = (TxnStatus::COMPLETE, TxnStatus::REVERSED);
+        let fields = doc! {
+            "reversed_by": reversal_id
+        };
+        let update = Self::transition_update(from, to, Some(creator), "Admin", fields)?;
+        let filter = Self::transition_filter(id, from);
+        let result = update_one_with_session!(&self.txn, update, None, session, filter)?;
+        if result.modified_count != 1 {
+            return Err(Status::Conflict);
+        }
+        self.post_entries_with_session(session, reversal_id, &reversal)?;
+
+        match txn_type {
+            TxnType::DEBIT => self.debit_with_session(session, &number, amount)?,
+            TxnType::CREDIT => self.credit_with_session(session, &number, amount)?,
+            TxnType::TRANSFER => {
+                let destination =
+                    option!(val -> destination; {val} | {return Err(Status::NotFound)});
+                self.debit_with_session(session, &number, amount)?;
+                self.credit_with_session(session, &destination, amount)?;
+            }
+        };
+        Ok(reversal_id)
+    }
+
+    fn apply_settlement(
+        &self,
+        session: &mut ClientSession,
+        id: ObjectId,
+        number: &str,
+        txn: &TRANSACTION,
+        actor: Option<ObjectId>,
+        role: &str,
+    ) -> Result<(), Status> {
+        let (from, to) = (TxnStatus::PENDING, TxnStatus::COMPLETE);
+        let update = Self::transition_update(from, to, actor, role, Document::new())?;
+        let filter = Self::transition_filter(id, from);
+        let result = update_one_with_session!(&self.txn, update, None, session, filter)?;
+        if result.modified_count != 1 {
+            return Err(Status::Conflict);
+        }
+        self.post_entries_with_session(session, id, txn)?;
+
+        match txn.txn_type {
+            TxnType::DEBIT => self.debit_with_session(session, number, txn.amount),
+            TxnType::CREDIT => self.credit_with_session(session, number, txn.amount),
+            TxnType::TRANSFER => {
+                let destination = option!(val -> txn.destination.as_ref(); {val} | {
+                    return Err(Status::NotFound)
+                });
+                self.debit_with_session(session, number, txn.amount)?;
+                self.credit_with_session(session, destination, txn.amount)
+            }
+        }
+    }
+
+    // Fails with 406 when the balance does not cover the amount
+    fn debit_with_session(
+        &self,
+        session: &mut ClientSession,
+        number: &str,
+        amount: i64,
+    ) -> Result<(), Status> {
+        let query = doc! {
+            "number": number,
+            "balance": { "$gte": amount }
+        };
+        let update = doc! {
+            "$inc": {
+                "balance": -amount
+            }
+        };
+        let result = update_one_with_session!(&self.account, update, None, session, query)?;
+        match result.matched_count {
+            1 => Ok(()),
+            _ => Err(Status::NotAcceptable),
+        }
+    }
+
+    // Fails with 403 when the account cannot receive, frozen and closed accounts take nothing in
+    fn credit_with_session(
+        &self,
+        session: &mut ClientSession,
+        number: &str,
+        amount: i64,
+    ) -> Result<(), Status> {
+        let query = doc! {
+            "number": number,
+            "status": {
+                "$in": [AccountStatus::ACTIVE.to_string(), AccountStatus::DORMANT.to_string()]
+            }
+        };
+        let update = doc! {
+            "$inc": {
+                "balance": amount
+            }
+        };
+        let result = update_one_with_session!(&self.account, update, None, session, query)?;
+        match result.matched_count {
+            1 => Ok(()),
+            _ => Err(Status::Forbidden),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        database::repository::Repository,
+        models::transaction::TxnStatus::{COMPLETE, EXPIRED, PENDING, REVERSED},
+    };
+    use mongodb::bson::{doc, oid::ObjectId, Bson, Document};
+    use rocket::http::Status;
+
+    #[test]
+    fn filter_pins_the_from_status() {
+        // A second settle or reversal of the same id finds nothing once the status moved on
+        let id = ObjectId::new();
+        let settle = Repository::transition_filter(id, PENDING);
+        assert_eq!(settle, doc! { "_id": id, "status": "PENDING" });
+        let reverse = Repository::transition_filter(id, COMPLETE);
+        assert_eq!(reverse, doc! { "_id": id, "status": "COMPLETE" });
+    }
+
+    #[test]
+    fn update_sets_the_status_and_records_the_transition() {
+        let actor = ObjectId::new();
+        let fields = doc! { "reversed_by": ObjectId::new() };
+        let update =
+            Repository::transition_update(COMPLETE, REVERSED, Some(actor), "Admin", fields)
+                .unwrap();
+
+        let set = update.get_document("$set").unwrap();
+        assert_eq!(set.get_str("status"), Ok("REVERSED"));
+        assert!(set.contains_key("reversed_by"));
+
+        let push = update.get_document("$push").unwrap();
+        let transition = push.get_document("transitions").unwrap();
+        assert_eq!(transition.get_str("from"), Ok("COMPLETE"));
+        assert_eq!(transition.get_str("to"), Ok("REVERSED"));
+        assert_eq!(transition.get("actor"), Some(&Bson::ObjectId(actor)));
+        assert_eq!(transition.get_str("role"), Ok("Admin"));
+    }
+
+    #[test]
+    fn update_refuses_forbidden_transitions() {
+        let update =
+            Repository::transition_update(EXPIRED, COMPLETE, None, "System", Document::new());
+        assert_eq!(update, Err(Status::Conflict));
+        let update =
+            Repository::transition_update(COMPLETE, PENDING, None, "System", Document::new());
+        assert_eq!(update, Err(Status::Conflict));
     }
 }
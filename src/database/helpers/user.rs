@@ -1,13 +1,21 @@
 #![allow(dead_code)]
 use crate::{
-    find_one,
+    check_ok_500, check_result, find_one, find_one_and_update,
     models::{
+        helpers::common::{timestamp, timestamp_millis},
+        ledger::ENTRY,
+        notification::{Notice, CONTACT},
         token::{JWT, TOKEN},
-        user::ACCOUNT,
+        user::{AccountStatus, Device, ACCOUNT, PAYOUT},
     },
-    update_one,
+    option, update_one, update_one_with_session,
+    utilities::crypto::{totp, verify_totp},
+};
+use mongodb::{
+    bson::{doc, oid::ObjectId, to_bson, Bson},
+    options::{FindOneAndUpdateOptions, ReturnDocument},
+    sync::ClientSession,
 };
-use mongodb::bson::{doc, oid::ObjectId};
 use rocket::http::Status;
 
 use crate::database::repository::Repository;
@@ -22,7 +30,12 @@ impl Repository {
     }
 
     pub async fn login_account(&self, sub: ObjectId, number: String) -> Result<String, Status> {
-        let token = TOKEN::new(sub.to_string(), "User".to_string()).unwrap();
+        let token = TOKEN::new(
+            sub.to_string(),
+            "User".to_string(),
+            self.settings.access_token_expiry,
+        )
+        .unwrap();
         let mut jwt = JWT::token_to_jwt(&token, &self.keys)?;
         let id = self.insert_token(jwt.clone(), sub).await?;
         jwt.set_id(id);
@@ -42,27 +55,389 @@ impl Repository {
         }
     }
 
-    pub async fn credit_amount(&self, number: String, amount: i64) -> Result<(), Status> {
+    // Remembers the device and tells the account holder when it was not seen before
+    pub async fn record_device(&self, account: &ACCOUNT, device: &Device) -> Result<(), Status> {
+        if account.knows_device(device) {
+            return Ok(());
+        }
+        let number = account.number.as_ref().unwrap();
         let update = doc! {
-            "$inc": {
-                "balance": amount
+            "$push": {
+                "devices": {
+                    "$each": [&device.fingerprint],
+                    "$slice": -ACCOUNT::MAX_DEVICES
+                }
             }
         };
-        match update_one!(&self.account, update, None, ("number", number)) {
-            Ok(_) => Ok(()),
-            Err(_) => Err(Status::InternalServerError),
+        let known = doc! {
+            "$ne": &device.fingerprint
+        };
+        let result = update_one!(
+            &self.account,
+            update,
+            None,
+            ("number", number),
+            ("devices", known)
+        )?;
+
+        // The first login has nothing to compare with
+        if result.modified_count == 1 && !account.devices.is_empty() {
+            let body = format!(
+                "Your account was logged into from a new device ({}). If this wasn't you, change your password",
+                device.agent
+            );
+            self.notify(account, Notice::NewDevice, body);
         }
+        Ok(())
     }
 
-    pub async fn debit_amount(&self, number: String, amount: i64) -> Result<(), Status> {
+    // Blocks new transactions once the account collects too many lockouts
+    pub async fn record_lockout(&self, number: &str) -> Result<(), Status> {
+        let options = FindOneAndUpdateOptions::builder()
+            .return_document(ReturnDocument::After)
+            .build();
         let update = doc! {
             "$inc": {
-                "balance": -amount
+                "lockouts": 1
+            }
+        };
+        let account: ACCOUNT =
+            find_one_and_update!(&self.account, options, update, ("number", number))?;
+        if account.lockouts < self.settings.max_lockouts {
+            return Ok(());
+        }
+
+        let update = doc! {
+            "$set": {
+                "lockouts": 0,
+                "blocked_until": timestamp_millis() + self.settings.lockout_duration
             }
         };
         match update_one!(&self.account, update, None, ("number", number)) {
             Ok(_) => Ok(()),
-            Err(_) => Err(Status::InternalServerError),
+            Err(error) => Err(error),
+        }
+    }
+
+    // Rehashes a PIN stored before hashing the first time it is entered correctly
+    pub async fn verify_pin(&self, account: &ACCOUNT, pin: &str) -> Result<bool, Status> {
+        if !account.verify_pin(pin)? {
+            return Ok(false);
+        }
+        if !account.is_pin_hashed() {
+            let mut data = account.clone();
+            data.hash_pin(pin)?;
+            let update = doc! {
+                "$set": {
+                    "pin": data.pin.as_ref().unwrap()
+                }
+            };
+            let number = account.number.as_ref().unwrap();
+            check_result!(
+                update_one!(&self.account, update, None, ("number", number)),
+                "Rehashing PIN"
+            );
+        }
+        Ok(true)
+    }
+
+    // Stores the already hashed PIN of the given account
+    pub async fn set_pin(
+        &self,
+        creator: ObjectId,
+        role: &str,
+        account: &ACCOUNT,
+    ) -> Result<(), Status> {
+        if !account.is_pin_hashed() {
+            return Err(Status::InternalServerError);
+        }
+        let update = doc! {
+            "$set": {
+                "pin": account.pin.as_ref().unwrap(),
+                "pin_temporary": account.pin_temporary
+            }
+        };
+        let number = account.number.as_ref().unwrap();
+        let result = update_one!(&self.account, update, None, ("number", number))?;
+        if result.matched_count == 0 {
+            return Err(Status::NotFound);
         }
+
+        // The hashes themselves are never logged
+        let sealed = Bson::String("$SEALED$".to_string());
+        let id = option!(val -> account.id; {val} | {return Err(Status::InternalServerError)});
+        self.log_update(creator, role, id, "pin", sealed.clone(), sealed)
+            .await;
+        Ok(())
+    }
+
+    // Throws 404 and 500
+    pub async fn set_contact(
+        &self,
+        creator: ObjectId,
+        role: &str,
+        account: &ACCOUNT,
+        contact: &CONTACT,
+    ) -> Result<(), Status> {
+        let new = check_ok_500!(to_bson(contact), "Serializing Contact")?;
+        let update = doc! {
+            "$set": {
+                "contact": new.clone()
+            }
+        };
+        let number = account.number.as_ref().unwrap();
+        let result = update_one!(&self.account, update, None, ("number", number))?;
+        if result.matched_count == 0 {
+            return Err(Status::NotFound);
+        }
+
+        let old = match &account.contact {
+            Some(contact) => to_bson(contact).unwrap_or(Bson::Null),
+            None => Bson::Null,
+        };
+        let id = option!(val -> account.id; {val} | {return Err(Status::InternalServerError)});
+        self.log_update(creator, role, id, "contact", old, new)
+            .await;
+        Ok(())
+    }
+
+    // Throws 404, 406, 409 and 500, leaving ACTIVE signs the account out and rejects its pending transactions
+    pub async fn set_account_status(
+        &self,
+        creator: ObjectId,
+        account: &ACCOUNT,
+        status: AccountStatus,
+        payout: Option<PAYOUT>,
+    ) -> Result<(), Status> {
+        let id = option!(val -> account.id; {val} | {return Err(Status::NotFound)});
+        let number =
+            option!(val -> account.number.as_ref(); {val} | {return Err(Status::NotFound)});
+        if !account.status.can_change_to(status) {
+            return Err(Status::NotAcceptable);
+        }
+
+        // Money left on a closing account has to be accounted for
+        let payout = match (status, account.balance) {
+            (AccountStatus::CLOSED, balance) if balance != 0 => {
+                let mut payout =
+                    option!(val -> payout; {val} | {return Err(Status::NotAcceptable)});
+                if balance < 0 {
+                    return Err(Status::NotAcceptable);
+                }
+                payout.amount = balance;
+                payout.entry = Some(ObjectId::new());
+                payout.timestamp = timestamp_millis();
+                Some(payout)
+            }
+            _ => None,
+        };
+
+        match &payout {
+            Some(payout) => {
+                let mut session =
+                    check_ok_500!(self.client.start_session(None), "Starting Session")?;
+                check_ok_500!(session.start_transaction(None), "Starting Payout")?;
+                match self.apply_payout(&mut session, id, number, account.balance, payout) {
+                    Ok(_) => check_ok_500!(session.commit_transaction(), "Committing Payout")?,
+                    Err(error) => {
+                        check_result!(session.abort_transaction(), "Aborting Payout");
+                        return Err(error);
+                    }
+                }
+            }
+            None => {
+                let mut query = doc! {
+                    "_id": id,
+                    "status": { "$ne": AccountStatus::CLOSED.to_string() }
+                };
+                if status == AccountStatus::CLOSED {
+                    query.insert("balance", 0i64);
+                }
+                let update = doc! {
+                    "$set": {
+                        "status": status.to_string()
+                    }
+                };
+                let result = update_one!(&self.account, update, None, query)?;
+                if result.matched_count != 1 {
+                    return Err(Status::Conflict);
+                }
+            }
+        }
+
+        if status != AccountStatus::ACTIVE {
+            self.revoke_account_token(account).await?;
+            check_result!(
+                self.reject_all_pending_txn("account", number, Some(creator), "Admin")
+                    .await,
+                "Reject All Pending Txn"
+            );
+        }
+        // Transfers waiting to pay into an account that can no longer receive are refused as well
+        if matches!(status, AccountStatus::FROZEN | AccountStatus::CLOSED) {
+            check_result!(
+                self.reject_all_pending_txn("destination", number, Some(creator), "Admin")
+                    .await,
+                "Reject All Pending Txn"
+            );
+        }
+
+        let (old, new) = (account.status.to_string(), status.to_string());
+        self.log_update(
+            creator,
+            "Admin",
+            id,
+            "status",
+            Bson::String(old),
+            Bson::String(new),
+        )
+        .await;
+        if let Some(payout) = &payout {
+            let old = Bson::Int64(account.balance);
+            self.log_update(creator, "Admin", id, "balance", old, Bson::Int64(0))
+                .await;
+            let new = to_bson(payout).unwrap_or(Bson::Null);
+            self.log_update(creator, "Admin", id, "payout", Bson::Null, new)
+                .await;
+        }
+        Ok(())
+    }
+
+    // Closes the account and posts the payout to the ledger together, the balance must not have moved
+    fn apply_payout(
+        &self,
+        session: &mut ClientSession,
+        id: ObjectId,
+        number: &str,
+        balance: i64,
+        payout: &PAYOUT,
+    ) -> Result<(), Status> {
+        let entry = option!(val -> payout.entry; {val} | {return Err(Status::InternalServerError)});
+        let query = doc! {
+            "_id": id,
+            "status": { "$ne": AccountStatus::CLOSED.to_string() },
+            "balance": balance
+        };
+        let update = doc! {
+            "$set": {
+                "status": AccountStatus::CLOSED.to_string(),
+                "balance": 0i64,
+                "payout": check_ok_500!(to_bson(payout))?
+            }
+        };
+        let result = update_one_with_session!(&self.account, update, None, session, query)?;
+        if result.matched_count != 1 {
+            return Err(Status::Conflict);
+        }
+        let entries = ENTRY::for_payout(entry, number, payout);
+        check_ok_500!(
+            self.ledger.insert_many_with_session(entries, None, session),
+            "Posting Payout"
+        )?;
+        Ok(())
+    }
+
+    // Throws 500, the token and every refresh token stop working as soon as they are gone
+    pub async fn revoke_account_token(&self, account: &ACCOUNT) -> Result<(), Status> {
+        if let Some(id) = account.id {
+            self.revoke_refresh_tokens(id).await?;
+        }
+        let token = match account.token {
+            Some(token) => token,
+            None => return Ok(()),
+        };
+        check_ok_500!(
+            self.token.delete_one(doc! { "_id": token }, None),
+            "Revoking Token"
+        )?;
+        let id = option!(val -> account.id; {val} | {return Err(Status::InternalServerError)});
+        let update = doc! {
+            "$unset": {
+                "token": ""
+            }
+        };
+        update_one!(&self.account, update, None, ("_id", id))?;
+        Ok(())
+    }
+
+    // Accounts created before one-time passwords were time based get their secret on first use
+    pub async fn get_totp_secret(&self, account: &ACCOUNT) -> Result<Vec<u8>, Status> {
+        if account.totp_secret.is_some() {
+            return account.totp_secret(&self.keys);
+        }
+
+        let number = account.number.as_ref().unwrap();
+        let mut data = account.clone();
+        data.generate_totp_secret(&self.keys)?;
+        let update = doc! {
+            "$set": {
+                "totp_secret": data.totp_secret.as_ref().unwrap()
+            }
+        };
+        let query = doc! {
+            "number": number,
+            "totp_secret": { "$exists": false }
+        };
+        update_one!(&self.account, update, None, query)?;
+
+        // Another request may have stored its secret first
+        self.get_account(number.to_owned())
+            .await?
+            .totp_secret(&self.keys)
+    }
+
+    pub async fn get_current_otp(&self, account: &ACCOUNT) -> Result<String, Status> {
+        let secret = self.get_totp_secret(account).await?;
+        let counter = timestamp() as u64 / self.settings.totp_step;
+        Ok(totp(&secret, counter, self.settings.totp_digits))
+    }
+
+    // Every code works once, only a later step than the last accepted one gets through
+    pub async fn verify_otp(&self, account: &ACCOUNT, code: &str) -> Result<bool, Status> {
+        let secret = self.get_totp_secret(account).await?;
+        let settings = &self.settings;
+        let counter = match verify_totp(
+            &secret,
+            code,
+            timestamp() as u64,
+            settings.totp_step,
+            settings.totp_digits,
+            settings.totp_skew,
+        ) {
+            Some(counter) => counter as i64,
+            None => return Ok(false),
+        };
+
+        let id = option!(val -> account.id; {val} | {return Err(Status::InternalServerError)});
+        let update = doc! {
+            "$set": {
+                "totp_counter": counter
+            }
+        };
+        let query = doc! {
+            "_id": id,
+            "$or": [
+                { "totp_counter": { "$exists": false } },
+                { "totp_counter": { "$lt": counter } }
+            ]
+        };
+        let result = update_one!(&self.account, update, None, query)?;
+        Ok(result.modified_count == 1)
+    }
+
+    // Throws 500, false when the provisioning uri was handed out before
+    pub async fn enroll_totp(&self, account: &ACCOUNT) -> Result<bool, Status> {
+        let id = option!(val -> account.id; {val} | {return Err(Status::InternalServerError)});
+        let update = doc! {
+            "$set": {
+                "totp_enrolled": true
+            }
+        };
+        let query = doc! {
+            "_id": id,
+            "totp_enrolled": { "$ne": true }
+        };
+        let result = update_one!(&self.account, update, None, query)?;
+        Ok(result.modified_count == 1)
     }
 }
@@ -0,0 +1,185 @@
+use crate::{
+    check_ok_500,
+    database::repository::Repository,
+    find_many, find_one, insert_one, log_action,
+    models::{
+        helpers::common::timestamp_millis,
+        transaction::{TxnStatus, TRANSACTION},
+        webhook::{DeliveryStatus, WebhookEvent, DELIVERY, WEBHOOK},
+    },
+    option, update_many, update_one,
+};
+use mongodb::{
+    bson::{doc, oid::ObjectId, Bson, Document},
+    options::FindOptions,
+    sync::ClientSession,
+};
+use rocket::{http::Status, serde::json::to_string};
+
+impl Repository {
+    // Throws 409 and 500, returns the id of the webhook
+    pub async fn create_webhook(
+        &self,
+        creator: ObjectId,
+        data: WEBHOOK,
+    ) -> Result<ObjectId, Status> {
+        let url = data.url.to_owned();
+        let id = insert_one!(&self.webhooks, data, None)?.inserted_id;
+        let id =
+            option!(val -> id.as_object_id(); {val} | {return Err(Status::InternalServerError)});
+        log_action!(&self.logs, creator, timestamp_millis(), url, "Admin", id);
+        Ok(id)
+    }
+
+    pub async fn get_webhooks(&self) -> Result<Vec<WEBHOOK>, Status> {
+        let filter = Document::new();
+        find_many!(&self.webhooks, None, filter)
+    }
+
+    pub async fn get_webhook_from_id(&self, id: &str) -> Result<WEBHOOK, Status> {
+        find_one!(&self.webhooks, None, _id => id)
+    }
+
+    // Throws 404 and 500, deliveries still waiting are given up
+    pub async fn delete_webhook(&self, creator: ObjectId, id: &str) -> Result<(), Status> {
+        let webhook = self.get_webhook_from_id(id).await?;
+        let id = option!(val -> webhook.id; {val} | {return Err(Status::InternalServerError)});
+        let result = check_ok_500!(self.webhooks.delete_one(doc! { "_id": id }, None))?;
+        if result.deleted_count != 1 {
+            return Err(Status::NotFound);
+        }
+
+        let update = doc! {
+            "$set": {
+                "status": DeliveryStatus::FAILED.to_string(),
+                "last_error": "Webhook Removed"
+            }
+        };
+        update_many!(
+            &self.deliveries,
+            update,
+            None,
+            ("webhook", id),
+            ("status", DeliveryStatus::PENDING.to_string())
+        )?;
+        self.log_update(
+            creator,
+            "Admin",
+            id,
+            "url",
+            Bson::String(webhook.url),
+            Bson::Null,
+        )
+        .await;
+        Ok(())
+    }
+
+    // One delivery per subscribed webhook, none when the status is not a webhook event
+    fn webhook_deliveries(
+        &self,
+        txn: &TRANSACTION,
+        status: TxnStatus,
+    ) -> Result<Vec<DELIVERY>, Status> {
+        let event = option!(val -> WEBHOOK::event_name(status); {val} | {return Ok(Vec::new())});
+        let filter = Document::new();
+        let webhooks: Vec<WEBHOOK> = find_many!(&self.webhooks, None, filter)?;
+        let payload = check_ok_500!(
+            to_string(&WebhookEvent::new(event, txn, status)),
+            "Serializing Webhook"
+        )?;
+        Ok(webhooks
+            .iter()
+            .filter(|webhook| webhook.subscribes(status))
+            .filter_map(|webhook| webhook.id)
+            .map(|id| DELIVERY::new(id, event, payload.to_owned()))
+            .collect())
+    }
+
+    // Throws 500, stores the deliveries in the session of the status change so both commit together
+    pub fn enqueue_webhooks_with_session(
+        &self,
+        session: &mut ClientSession,
+        txn: &TRANSACTION,
+        status: TxnStatus,
+    ) -> Result<(), Status> {
+        let deliveries = self.webhook_deliveries(txn, status)?;
+        if deliveries.is_empty() {
+            return Ok(());
+        }
+        check_ok_500!(
+            self.deliveries
+                .insert_many_with_session(deliveries, None, session),
+            "Enqueueing Webhooks"
+        )?;
+        Ok(())
+    }
+
+    // Stores one delivery per subscribed webhook so events survive a restart
+    pub fn enqueue_webhooks(&self, txn: &TRANSACTION, status: TxnStatus) {
+        let deliveries = match self.webhook_deliveries(txn, status) {
+            Ok(deliveries) => deliveries,
+            Err(error) => {
+                println!("Webhook Error: {error}");
+                return;
+            }
+        };
+        if deliveries.is_empty() {
+            return;
+        }
+        if let Err(error) = self.deliveries.insert_many(deliveries, None) {
+            println!("Webhook Error: {error}");
+        }
+    }
+
+    // Oldest first
+    pub async fn get_due_deliveries(&self, limit: i64) -> Result<Vec<DELIVERY>, Status> {
+        let options = FindOptions::builder()
+            .sort(doc! {
+                "next_attempt": 1
+            })
+            .limit(limit)
+            .build();
+        let filter = doc! {
+            "status": DeliveryStatus::PENDING.to_string(),
+            "next_attempt": { "$lte": timestamp_millis() }
+        };
+        find_many!(&self.deliveries, options, filter)
+    }
+
+    // Retries with exponential backoff until the attempts run out
+    pub async fn record_delivery(
+        &self,
+        delivery: &DELIVERY,
+        result: Result<(), String>,
+    ) -> Result<(), Status> {
+        let id = option!(val -> delivery.id; {val} | {return Err(Status::NotFound)});
+        let attempts = delivery.attempts + 1;
+        let update = match result {
+            Ok(_) => doc! {
+                "$set": {
+                    "status": DeliveryStatus::DELIVERED.to_string(),
+                    "attempts": attempts
+                },
+                "$unset": {
+                    "last_error": ""
+                }
+            },
+            Err(error) => {
+                let status = match attempts < self.settings.webhook_max_attempts {
+                    true => DeliveryStatus::PENDING,
+                    false => DeliveryStatus::FAILED,
+                };
+                doc! {
+                    "$set": {
+                        "status": status.to_string(),
+                        "attempts": attempts,
+                        "next_attempt": timestamp_millis() + self.settings.webhook_delay(attempts),
+                        "last_error": error
+                    }
+                }
+            }
+        };
+        update_one!(&self.deliveries, update, None, ("_id", id))?;
+        Ok(())
+    }
+}
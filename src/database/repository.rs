@@ -1,24 +1,53 @@
+use crate::database::helpers::atm::geojson_migration;
 use crate::database::helpers::indexes::{
-    account_indexes, admin_indexes, atm_indexes, token_indexes,
+    account_indexes, admin_indexes, atm_geo_indexes, atm_indexes, delivery_indexes,
+    idempotency_expiry_indexes, idempotency_indexes, ledger_indexes, refresh_expiry_indexes,
+    refresh_indexes, refresh_sub_indexes, token_indexes, txn_account_indexes, txn_atm_indexes,
+    txn_destination_indexes,
+};
+use crate::models::{
+    atm::ATM,
+    events::TxnEvent,
+    idempotency::IDEMPOTENCY,
+    keys::KEY,
+    ledger::ENTRY,
+    permission::PERMISSIONS,
+    settings::SETTINGS,
+    token::{JWT, REFRESH},
+    transaction::TRANSACTION,
+    user::ACCOUNT,
+    webhook::{DELIVERY, WEBHOOK},
 };
-use crate::models::{atm::ATM, keys::KEY, token::JWT, transaction::TRANSACTION, user::ACCOUNT};
 use crate::resolve_result;
+use crate::utilities::notifications::Notifier;
 use dotenv::dotenv;
 use mongodb::bson::doc;
 use mongodb::{sync::Client, sync::Collection};
+use rocket::tokio::sync::broadcast::{self, Sender};
 use std::collections::HashMap;
 use std::env;
 
 use crate::models::{admin::ADMIN, logs::LOG};
 
+#[derive(Clone)]
 pub struct Repository {
+    pub client: Client,
     pub admin: Collection<ADMIN>,
     pub atm: Collection<ATM>,
     pub account: Collection<ACCOUNT>,
     pub txn: Collection<TRANSACTION>,
     pub token: Collection<JWT>,
+    pub refresh: Collection<REFRESH>, // Hashes of the refresh tokens handed out
     pub logs: Collection<LOG>,
+    pub idempotency: Collection<IDEMPOTENCY>,
+    pub ledger: Collection<ENTRY>,
+    pub webhooks: Collection<WEBHOOK>,
+    pub deliveries: Collection<DELIVERY>, // Outbox of webhook events
     pub keys: KEY,
+    pub settings: SETTINGS,
+    pub events: Sender<TxnEvent>, // Status changes pushed to the event streams
+    pub notifier: Notifier,       // Transports for account holder notifications
+    pub permissions: PERMISSIONS, // What each admin role may do
 }
 
 impl Repository {
@@ -46,27 +75,94 @@ impl Repository {
         let txn = database.collection("transaction");
         let logs = database.collection("logs");
         let token = database.collection("token");
+        let refresh = database.collection("refresh");
+        let idempotency = database.collection("idempotency");
+        let ledger = database.collection("ledger");
+        let webhooks = database.collection("webhook");
+        let deliveries = database.collection("delivery");
         let keys = KEY::retrive_keys();
+        let settings = SETTINGS::load();
+        let (events, _) = broadcast::channel(TxnEvent::CAPACITY);
+        let notifier = Notifier::load();
+        let permissions = PERMISSIONS::load();
+
+        // Has to run before the 2dsphere index is built
+        let (query, pipeline) = geojson_migration();
+        match atm.update_many(query, pipeline, None) {
+            Ok(result) => println!(
+                "Converted {} ATM locations to GeoJSON",
+                result.modified_count
+            ),
+            Err(error) => println!("Converting ATM locations failed: {error}"),
+        }
 
         let mut index_results = HashMap::new();
         index_results.insert("ADMIN", admin.create_index(admin_indexes(), None));
         index_results.insert("ACCOUNT", account.create_index(account_indexes(), None));
         index_results.insert("ATM", atm.create_index(atm_indexes(), None));
+        index_results.insert("ATM_GEO", atm.create_index(atm_geo_indexes(), None));
         index_results.insert("TOKEN", token.create_index(token_indexes(), None));
+        index_results.insert("REFRESH", refresh.create_index(refresh_indexes(), None));
+        index_results.insert(
+            "REFRESH_SUB",
+            refresh.create_index(refresh_sub_indexes(), None),
+        );
+        index_results.insert(
+            "REFRESH_EXPIRY",
+            refresh.create_index(refresh_expiry_indexes(), None),
+        );
+        index_results.insert(
+            "TRANSACTION_ACCOUNT",
+            txn.create_index(txn_account_indexes(), None),
+        );
+        index_results.insert("TRANSACTION_ATM", txn.create_index(txn_atm_indexes(), None));
+        index_results.insert(
+            "TRANSACTION_DESTINATION",
+            txn.create_index(txn_destination_indexes(), None),
+        );
+        index_results.insert("LEDGER", ledger.create_index(ledger_indexes(), None));
+        index_results.insert(
+            "DELIVERY",
+            deliveries.create_index(delivery_indexes(), None),
+        );
+        index_results.insert(
+            "IDEMPOTENCY",
+            idempotency.create_index(idempotency_indexes(), None),
+        );
+        index_results.insert(
+            "IDEMPOTENCY_EXPIRY",
+            idempotency.create_index(idempotency_expiry_indexes(), None),
+        );
 
         for (name, result) in index_results {
             println!("Collection Name: {name}");
             println!("Result: {result:#?}");
         }
 
-        Ok(Self {
+        let repository = Self {
+            client,
             admin,
             atm,
             account,
             txn,
             logs,
             token,
+            refresh,
+            idempotency,
+            ledger,
+            webhooks,
+            deliveries,
             keys,
-        })
+            settings,
+            events,
+            notifier,
+            permissions,
+        };
+
+        match repository.post_opening_balances().await {
+            Ok(count) => println!("Posted {count} opening balances to the ledger"),
+            Err(error) => println!("Posting opening balances failed: {error}"),
+        }
+        Ok(repository)
     }
 }
@@ -13,9 +13,12 @@ use database::repository::Repository;
 use dotenv::dotenv;
 use errors::catchers::*;
 use rocket::Config;
-use routes::{create::*, details::*, login::*, transaction::*};
+use routes::{
+    cassette::*, contact::*, create::*, details::*, events::*, health::*, history::*, ledger::*,
+    login::*, manage::*, pin::*, transaction::*, webhook::*,
+};
 use std::{env, net::Ipv4Addr, str::FromStr};
-use utilities::cors::*;
+use utilities::{cors::*, sweeper::spawn_txn_sweeper, webhooks::spawn_webhook_dispatcher};
 
 // TODO -> Use resolve_result macro in place of match clauses
 
@@ -27,6 +30,8 @@ async fn rocket() -> _ {
         Ok(repository) => repository,
         Err(error) => panic!("Failed to initialize repository: {}", error),
     };
+    spawn_txn_sweeper(repository.clone());
+    spawn_webhook_dispatcher(repository.clone());
 
     let url = resolve_result!(name, _ -> env::var("URL"); {name} | {
         panic!("Couldn't Load Variable URL")
@@ -55,24 +60,75 @@ async fn rocket() -> _ {
             ],
         )
         .mount("/", routes![route_options])
-        .mount("/", routes![login_admin, login_atm, login_account])
+        .mount(
+            "/",
+            routes![login_admin, login_atm, login_account, refresh_token, logout],
+        )
         .mount("/", routes![create_admin, create_atm, create_account])
         .mount(
             "/",
-            routes![get_atm, get_account, get_atm_admin, get_account_admin],
+            routes![
+                get_atm,
+                get_account,
+                get_atm_admin,
+                get_account_admin,
+                get_nearest_atms
+            ],
+        )
+        .mount(
+            "/",
+            routes![create_txn_account, create_txn_transfer, create_txn_atm],
         )
-        .mount("/", routes![create_txn_account, create_txn_atm])
         .mount("/", routes![confirm_txn_account, confirm_txn_atm])
+        .mount("/", routes![get_txn_events_atm, get_txn_events_account])
         .mount(
             "/",
             routes![
                 get_otp_account,
-                get_otp_atm,
+                get_totp_uri,
                 get_txn_status_atm,
                 get_txn_recent_value
             ],
         )
-        .mount("/", routes![reject_atm_txn])
+        .mount("/", routes![reject_atm_txn, reverse_txn_admin])
+        .mount("/", routes![change_pin, reset_pin_admin])
+        .mount("/", routes![update_contact])
+        .mount("/", routes![report_heartbeat, set_atm_service])
+        .mount(
+            "/",
+            routes![
+                update_atm,
+                deactivate_atm,
+                activate_atm,
+                delete_atm,
+                set_account_status
+            ],
+        )
+        .mount(
+            "/",
+            routes![
+                get_admins,
+                change_admin_role,
+                disable_admin,
+                enable_admin,
+                reset_admin_password
+            ],
+        )
+        .mount("/", routes![reconcile_ledger])
+        .mount("/", routes![create_webhook, get_webhooks, delete_webhook])
+        .mount(
+            "/",
+            routes![load_cassettes, adjust_cassette, report_dispense],
+        )
+        .mount(
+            "/",
+            routes![
+                get_txn_history_account,
+                get_txn_history_atm,
+                get_txn_history_admin,
+                get_txn_admin
+            ],
+        )
 }
 
 // Testing
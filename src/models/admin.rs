@@ -35,6 +35,22 @@ pub struct ADMIN {
     pub role: Option<Role>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub token: Option<ObjectId>, // Token
+    #[serde(default = "default_active")]
+    pub active: bool, // Disabled admins cannot log in
+}
+
+fn default_active() -> bool {
+    true
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RoleChange {
+    pub role: Role,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PasswordReset {
+    pub password: String,
 }
 
 impl ADMIN {
@@ -50,6 +66,7 @@ impl ADMIN {
             password,
             role,
             token: None,
+            active: default_active(),
         }
     }
 }
@@ -8,17 +8,117 @@ use std::collections::HashMap;
 pub enum Field {
     Latitude,
     Longitude,
+    Type,        // GeoJSON
+    Coordinates, // GeoJSON, longitude first
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, Clone, Copy)]
 pub struct Longitude(pub i32, pub i32, pub f32);
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, Clone, Copy)]
 pub struct Latitude(pub i32, pub i32, pub f32);
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub struct Location(pub Latitude, pub Longitude);
 
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub struct CASSETTE {
+    pub denomination: i64,
+    pub count: i64, // Notes loaded
+}
+
+#[allow(non_camel_case_types)]
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum AtmState {
+    ONLINE,         // Heartbeat on time and every device fine
+    DEGRADED,       // Heartbeat on time but a device needs attention
+    OFFLINE,        // No heartbeat within HEARTBEAT_TIMEOUT
+    OUT_OF_SERVICE, // Taken out of service by an admin
+}
+
+#[allow(non_camel_case_types)]
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum PrinterState {
+    OK,
+    LOW_PAPER,
+    NO_PAPER,
+    FAULT,
+}
+
+/// Device status an ATM reports on a schedule
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct HEARTBEAT {
+    pub cash: i64, // Value of the notes the machine counts in its cassettes
+    pub printer: PrinterState,
+    pub version: String, // Software running on the machine
+    #[serde(default)]
+    pub timestamp: i64, // Millis, set by the server on arrival
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ServiceChange {
+    pub out_of_service: bool,
+}
+
+/// Fields an admin can change on an existing ATM, the ones left out stay as they are
+#[derive(Debug, Deserialize)]
+pub struct AtmUpdate {
+    pub branch: Option<String>,
+    pub address: Option<String>,
+    pub coordinates: Option<Location>,
+    pub password: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CassetteAdjustment {
+    pub denomination: i64,
+    pub delta: i64, // Notes added, negative to remove
+}
+
+fn default_cassettes() -> Vec<CASSETTE> {
+    Vec::new()
+}
+
+fn default_state() -> AtmState {
+    AtmState::OFFLINE
+}
+
+fn default_active() -> bool {
+    true
+}
+
+pub use self::nearby::NearbyFilter;
+
+#[allow(renamed_and_removed_lints)]
+mod nearby {
+    #[derive(Debug, FromForm)]
+    pub struct NearbyFilter {
+        pub latitude: f32,
+        pub longitude: f32,
+        pub limit: Option<i64>,
+        pub branch: Option<String>,
+        pub in_service: Option<bool>,
+    }
+}
+
+/// An ATM found around a point, without anything private
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct NEARBY {
+    pub name: String,
+    pub branch: String,
+    pub address: String,
+    pub coordinates: Location,
+    pub distance: f64, // Meters
+    #[serde(default = "default_state")]
+    pub state: AtmState,
+    #[serde(default)]
+    pub in_service: bool,
+    #[serde(default, skip_serializing)]
+    pub cassettes: Vec<CASSETTE>, // Only read to tell if the ATM is in service
+    #[serde(default, skip_serializing)]
+    pub heartbeat: Option<HEARTBEAT>, // Only read to tell if the ATM is in service
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct ATM {
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
@@ -30,6 +130,14 @@ pub struct ATM {
     pub token: Option<ObjectId>,
     pub coordinates: Location,
     pub password: String,
+    #[serde(default = "default_cassettes")]
+    pub cassettes: Vec<CASSETTE>,
+    #[serde(default = "default_state")]
+    pub state: AtmState, // As of the last heartbeat or admin change, see ATM::current_state
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub heartbeat: Option<HEARTBEAT>,
+    #[serde(default = "default_active")]
+    pub active: bool, // Deactivated ATMs cannot log in or take transactions but keep their history
 }
 
 fn destructure_locale(decimal_degrees: f32, locale_type: &str) -> Result<(i32, i32, f32), String> {
@@ -43,7 +151,13 @@ fn destructure_locale(decimal_degrees: f32, locale_type: &str) -> Result<(i32, i
     let minutes = dec.fract() * 60.0;
     let seconds = minutes.fract() * 60.0;
 
-    Ok((decimal_degrees as i32, minutes.abs() as i32, seconds.abs()))
+    // Every part carries the sign so south and west survive the round trip, even below one degree
+    let sign = decimal_degrees.signum();
+    Ok((
+        decimal_degrees as i32,
+        (minutes as i32) * sign as i32,
+        seconds * sign,
+    ))
 }
 
 impl Latitude {
@@ -95,6 +209,10 @@ impl ATM {
             token: None,
             coordinates: location,
             password,
+            cassettes: default_cassettes(),
+            state: default_state(),
+            heartbeat: None,
+            active: default_active(),
         }
     }
 }
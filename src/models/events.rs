@@ -0,0 +1,18 @@
+use super::transaction::{TxnStatus, TxnType};
+use serde::Serialize;
+
+#[derive(Debug, Serialize, Clone)]
+pub struct TxnEvent {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(skip)]
+    pub account: Option<String>, // Used to pick the subscribers, never sent
+    #[serde(skip)]
+    pub atm: Option<String>, // Used to pick the subscribers, never sent
+    #[serde(skip)]
+    pub destination: Option<String>, // Receiving account of a transfer, never sent
+    pub txn_type: TxnType,
+    pub status: TxnStatus,
+    pub amount: i64,
+    pub timestamp: i64,
+}
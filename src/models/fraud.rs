@@ -0,0 +1,40 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Decision {
+    ALLOW,   // Goes through as usual
+    CONFIRM, // Needs the challenge sent to the account holder as well
+    BLOCK,   // Refused before it is stored
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum FraudRule {
+    VELOCITY, // Too many transactions in a short window
+    AMOUNT,   // Far above what the account usually moves
+    TRAVEL,   // Previous ATM too far away to have been reached in time
+    UNSEEN,   // First use of the ATM by the account
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct SIGNAL {
+    pub rule: FraudRule,
+    pub score: i32,
+    pub detail: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ASSESSMENT {
+    pub score: i32, // Sum of the signals
+    pub decision: Decision,
+    #[serde(default)]
+    pub signals: Vec<SIGNAL>,
+}
+
+/// What the rules look at, gathered from earlier transactions of the account
+#[derive(Debug, Default)]
+pub struct History {
+    pub recent: u64,                // Transactions inside the velocity window
+    pub amounts: Vec<i64>,          // Completed amounts of the same type, newest first
+    pub previous_uses: u64,         // Completed transactions at the same ATM
+    pub travel: Option<(f64, i64)>, // Meters from and millis since the last ATM used elsewhere
+}
@@ -1,6 +1,14 @@
 use rocket::{http::Status, serde::json::to_string};
 use serde::{Deserialize, Serialize};
 
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Paging {
+    pub limit: i64,
+    pub count: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next: Option<String>, // Cursor for the following page
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Response<T: Serialize + Clone> {
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -12,9 +20,13 @@ pub struct Response<T: Serialize + Clone> {
     #[serde(skip_serializing_if = "Option::is_none")]
     token: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    refresh: Option<String>, // Traded for the next token once this one expires
+    #[serde(skip_serializing_if = "Option::is_none")]
     error: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     status: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    paging: Option<Paging>,
 }
 
 impl<T: Serialize + Clone> Response<T> {
@@ -27,8 +39,10 @@ impl<T: Serialize + Clone> Response<T> {
             data: None,
             uri: None,
             token: None,
+            refresh: None,
             error: None,
             status: None,
+            paging: None,
         }
     }
 
@@ -62,6 +76,11 @@ impl<T: Serialize + Clone> Response<T> {
         self
     }
 
+    pub fn refresh(&mut self, refresh: String) -> &mut Self {
+        self.refresh = Some(refresh);
+        self
+    }
+
     pub fn error(&mut self, error: String) -> &mut Self {
         self.error = Some(error);
         self
@@ -71,6 +90,11 @@ impl<T: Serialize + Clone> Response<T> {
         self.status = Some(status.code);
         self
     }
+
+    pub fn paging(&mut self, paging: Paging) -> &mut Self {
+        self.paging = Some(paging);
+        self
+    }
 }
 
 impl<T: Serialize + Clone> Clone for Response<T> {
@@ -80,8 +104,10 @@ impl<T: Serialize + Clone> Clone for Response<T> {
             data: self.data.clone(),
             uri: self.uri.to_owned(),
             token: self.token.to_owned(),
+            refresh: self.refresh.to_owned(),
             error: self.error.to_owned(),
             status: self.status,
+            paging: self.paging.clone(),
         }
     }
 }
@@ -1,4 +1,4 @@
-use crate::models::admin::{Role, ADMIN};
+use crate::models::admin::{PasswordReset, Role, ADMIN};
 use std::{
     cmp::Ordering::{Equal, Greater, Less},
     str::FromStr,
@@ -85,6 +85,23 @@ impl ADMIN {
         }
         Ok(self)
     }
+
+    /// Admins only act on admins of a lower role
+    pub fn outranks(&self, other: &ADMIN) -> bool {
+        match (self.role, other.role) {
+            (Some(role), Some(other)) => role > other,
+            _ => false,
+        }
+    }
+}
+
+impl PasswordReset {
+    pub fn validate(&self) -> Result<&Self, String> {
+        if self.password.len() < 8 {
+            return Err("Password too short".to_string());
+        }
+        Ok(self)
+    }
 }
 
 impl Clone for ADMIN {
@@ -95,6 +112,7 @@ impl Clone for ADMIN {
             password: self.password.to_owned(),
             role: self.role,
             token: self.token.to_owned(),
+            active: self.active,
         }
     }
 }
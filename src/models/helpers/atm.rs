@@ -1,9 +1,17 @@
-use crate::models::atm::{Field, Latitude, Location, Longitude, ATM};
+use super::common::timestamp_millis;
+use crate::models::{
+    atm::{
+        AtmState, AtmUpdate, Field, Latitude, Location, Longitude, NearbyFilter, PrinterState, ATM,
+        CASSETTE, HEARTBEAT, NEARBY,
+    },
+    settings::SETTINGS,
+};
+use mongodb::bson::{doc, Document};
 use serde::{
     de::{self, Deserialize, Deserializer, MapAccess, SeqAccess, Visitor},
     ser::{Serialize, SerializeStruct},
 };
-use std::fmt;
+use std::{collections::HashSet, fmt};
 
 impl Latitude {
     pub fn to_decimal(&self) -> f32 {
@@ -17,14 +25,42 @@ impl Longitude {
     }
 }
 
+impl Location {
+    pub const EARTH_RADIUS: f64 = 6_371_008.8; // Mean radius in meters
+    pub const GEOJSON_TYPE: &str = "Point";
+
+    /// Longitude first, the order GeoJSON uses
+    pub fn to_geojson(&self) -> [f64; 2] {
+        [self.1.to_decimal() as f64, self.0.to_decimal() as f64]
+    }
+
+    /// Great-circle distance in meters, using the haversine formula
+    pub fn distance(&self, other: &Location) -> f64 {
+        let (latitude, longitude) = (
+            (self.0.to_decimal() as f64).to_radians(),
+            (self.1.to_decimal() as f64).to_radians(),
+        );
+        let (other_latitude, other_longitude) = (
+            (other.0.to_decimal() as f64).to_radians(),
+            (other.1.to_decimal() as f64).to_radians(),
+        );
+        let half_chord = ((other_latitude - latitude) / 2.0).sin().powi(2)
+            + latitude.cos()
+                * other_latitude.cos()
+                * ((other_longitude - longitude) / 2.0).sin().powi(2);
+        2.0 * Self::EARTH_RADIUS * half_chord.sqrt().min(1.0).asin()
+    }
+}
+
+// Written as a GeoJSON point so the 2dsphere index can use it
 impl Serialize for Location {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: serde::Serializer,
     {
         let mut state = serializer.serialize_struct("location", 2)?;
-        state.serialize_field("latitude", &self.0.to_decimal())?;
-        state.serialize_field("longitude", &self.1.to_decimal())?;
+        state.serialize_field("type", Location::GEOJSON_TYPE)?;
+        state.serialize_field("coordinates", &self.to_geojson())?;
         state.end()
     }
 }
@@ -53,7 +89,8 @@ impl<'de> Deserialize<'de> for Location {
                 let longitude_decimal_degrees = seq
                     .next_element()?
                     .ok_or_else(|| de::Error::invalid_length(1, &self))?;
-                Ok(Location::new(latitude_decimal_degrees, longitude_decimal_degrees).unwrap())
+                Location::new(latitude_decimal_degrees, longitude_decimal_degrees)
+                    .map_err(de::Error::custom)
             }
 
             fn visit_map<V>(self, mut map: V) -> Result<Location, V::Error>
@@ -63,8 +100,23 @@ impl<'de> Deserialize<'de> for Location {
                 let mut latitude_decimal_degrees: Option<f32> = None;
                 let mut longitude_decimal_degrees: Option<f32> = None;
 
+                // Either a GeoJSON point or the latitude and longitude fields
                 while let Some(key) = map.next_key()? {
                     match key {
+                        Field::Type => {
+                            let kind: String = map.next_value()?;
+                            if kind != Location::GEOJSON_TYPE {
+                                return Err(de::Error::custom("Only Point is supported"));
+                            }
+                        }
+                        Field::Coordinates => {
+                            if latitude_decimal_degrees.is_some() {
+                                return Err(de::Error::duplicate_field("coordinates"));
+                            }
+                            let [longitude, latitude]: [f32; 2] = map.next_value()?;
+                            latitude_decimal_degrees = Some(latitude);
+                            longitude_decimal_degrees = Some(longitude);
+                        }
                         Field::Latitude => {
                             if latitude_decimal_degrees.is_some() {
                                 return Err(de::Error::duplicate_field("latitude"));
@@ -83,11 +135,12 @@ impl<'de> Deserialize<'de> for Location {
                     latitude_decimal_degrees.ok_or_else(|| de::Error::missing_field("latitude"))?;
                 let longitude_decimal_degrees = longitude_decimal_degrees
                     .ok_or_else(|| de::Error::missing_field("longitude"))?;
-                Ok(Location::new(latitude_decimal_degrees, longitude_decimal_degrees).unwrap())
+                Location::new(latitude_decimal_degrees, longitude_decimal_degrees)
+                    .map_err(de::Error::custom)
             }
         }
 
-        const FIELDS: &'static [&'static str] = &["latitude", "longitude"];
+        const FIELDS: &'static [&'static str] = &["latitude", "longitude", "type", "coordinates"];
         deserializer.deserialize_struct("Location", FIELDS, LocationVisitor)
     }
 }
@@ -106,6 +159,296 @@ impl Clone for ATM {
             coordinates: Location::new(latitude_decimal_degrees, longitude_decimal_degrees)
                 .unwrap(),
             password: self.password.to_owned(),
+            cassettes: self.cassettes.clone(),
+            state: self.state,
+            heartbeat: self.heartbeat.clone(),
+            active: self.active,
+        }
+    }
+}
+
+impl CASSETTE {
+    /// Notes left in any cassette
+    pub fn any_loaded(cassettes: &[CASSETTE]) -> bool {
+        cassettes.iter().any(|cassette| cassette.count > 0)
+    }
+
+    /// Denominations have to be positive and unique, counts cannot be negative
+    pub fn validate(cassettes: &[CASSETTE]) -> Result<(), String> {
+        let mut denominations = HashSet::new();
+        for cassette in cassettes {
+            if cassette.denomination <= 0 {
+                return Err("Invalid Denomination".to_string());
+            }
+            if cassette.count < 0 {
+                return Err("Invalid Count".to_string());
+            }
+            if !denominations.insert(cassette.denomination) {
+                return Err("Duplicate Denomination".to_string());
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ATM {
+    pub const MAX_DISPENSE_UNITS: i64 = 1_000_000;
+
+    pub fn current_state(&self, settings: &SETTINGS) -> AtmState {
+        match self.active {
+            true => AtmState::current(self.state, self.heartbeat.as_ref(), settings),
+            false => AtmState::OUT_OF_SERVICE,
+        }
+    }
+
+    /// Query matching the ATMs that are in service, ONLINE with notes to pay out, or the ones that are not
+    pub fn in_service_query(in_service: bool, settings: &SETTINGS) -> Document {
+        let query = doc! {
+            "state": AtmState::ONLINE.to_string(),
+            "heartbeat.timestamp": { "$gte": timestamp_millis() - settings.heartbeat_timeout },
+            "cassettes": {
+                "$elemMatch": {
+                    "count": { "$gt": 0 }
+                }
+            }
+        };
+        match in_service {
+            true => query,
+            false => doc! { "$nor": [query] },
+        }
+    }
+
+    /// Checks a reported position against the ATM, a limit of 0 accepts any position
+    pub fn is_within(&self, location: &Location, limit: f64) -> bool {
+        limit <= 0.0 || self.coordinates.distance(location) <= limit
+    }
+
+    /// Checks if the loaded notes can make up the amount exactly
+    pub fn can_dispense(&self, amount: i64) -> bool {
+        if amount <= 0 {
+            return false;
+        }
+        let cassettes: Vec<&CASSETTE> = self
+            .cassettes
+            .iter()
+            .filter(|cassette| cassette.denomination > 0 && cassette.count > 0)
+            .collect();
+        let total: i64 = cassettes
+            .iter()
+            .map(|cassette| cassette.denomination.saturating_mul(cassette.count))
+            .fold(0, |total, value| total.saturating_add(value));
+        if total < amount {
+            return false;
+        }
+
+        // Work in units of the common divisor to keep the table small
+        let unit = cassettes
+            .iter()
+            .fold(0, |unit, cassette| gcd(unit, cassette.denomination));
+        if unit == 0 || amount % unit != 0 {
+            return false;
+        }
+        if amount / unit > ATM::MAX_DISPENSE_UNITS {
+            return false;
+        }
+        let target = (amount / unit) as usize;
+
+        // Bounded change making, used[value] is the notes of the current cassette spent to reach value
+        let mut reachable = vec![false; target + 1];
+        reachable[0] = true;
+        for cassette in cassettes {
+            let step = (cassette.denomination / unit) as usize;
+            let mut used = vec![0i64; target + 1];
+            for value in step..=target {
+                if !reachable[value]
+                    && reachable[value - step]
+                    && used[value - step] < cassette.count
+                {
+                    reachable[value] = true;
+                    used[value] = used[value - step] + 1;
+                }
+            }
+        }
+        reachable[target]
+    }
+}
+
+impl AtmUpdate {
+    pub const MAX_LENGTH: usize = 255;
+
+    pub fn validate(&self) -> Result<&Self, String> {
+        if self.branch.is_none()
+            && self.address.is_none()
+            && self.coordinates.is_none()
+            && self.password.is_none()
+        {
+            return Err("Nothing to update".to_string());
+        }
+        for value in [&self.branch, &self.address].into_iter().flatten() {
+            if value.trim().is_empty() || value.len() > Self::MAX_LENGTH {
+                return Err("Invalid Branch or Address".to_string());
+            }
         }
+        if let Some(password) = &self.password {
+            if password.len() < 8 {
+                return Err("Password too short".to_string());
+            }
+        }
+        Ok(self)
+    }
+}
+
+impl NEARBY {
+    pub fn with_status(mut self, settings: &SETTINGS) -> Self {
+        self.state = AtmState::current(self.state, self.heartbeat.as_ref(), settings);
+        self.in_service = self.state == AtmState::ONLINE && CASSETTE::any_loaded(&self.cassettes);
+        self
+    }
+}
+
+impl ToString for AtmState {
+    fn to_string(&self) -> String {
+        match self {
+            AtmState::ONLINE => "ONLINE".to_string(),
+            AtmState::DEGRADED => "DEGRADED".to_string(),
+            AtmState::OFFLINE => "OFFLINE".to_string(),
+            AtmState::OUT_OF_SERVICE => "OUT_OF_SERVICE".to_string(),
+        }
+    }
+}
+
+impl AtmState {
+    /// The stored state goes OFFLINE once the last heartbeat is too old, unless an admin took the ATM out of service
+    pub fn current(stored: AtmState, heartbeat: Option<&HEARTBEAT>, settings: &SETTINGS) -> Self {
+        if stored == AtmState::OUT_OF_SERVICE {
+            return stored;
+        }
+        match heartbeat {
+            Some(heartbeat)
+                if heartbeat.timestamp + settings.heartbeat_timeout >= timestamp_millis() =>
+            {
+                stored
+            }
+            _ => AtmState::OFFLINE,
+        }
+    }
+}
+
+impl HEARTBEAT {
+    pub const MAX_VERSION_LENGTH: usize = 64;
+
+    pub fn validate(&self) -> Result<&Self, String> {
+        if self.cash < 0 {
+            return Err("Invalid Cash".to_string());
+        }
+        if self.version.is_empty() || self.version.len() > Self::MAX_VERSION_LENGTH {
+            return Err("Invalid Version".to_string());
+        }
+        Ok(self)
+    }
+
+    /// Low paper still prints, an empty or broken printer and low cash need attention
+    pub fn health(&self, settings: &SETTINGS) -> AtmState {
+        let printer = matches!(self.printer, PrinterState::OK | PrinterState::LOW_PAPER);
+        match printer && self.cash >= settings.low_cash_level {
+            true => AtmState::ONLINE,
+            false => AtmState::DEGRADED,
+        }
+    }
+}
+
+impl NearbyFilter {
+    pub const DEFAULT_LIMIT: i64 = 5;
+    pub const MAX_LIMIT: i64 = 50;
+
+    pub fn limit(&self) -> i64 {
+        match self.limit {
+            Some(limit) if limit > 0 => limit.min(Self::MAX_LIMIT),
+            _ => Self::DEFAULT_LIMIT,
+        }
+    }
+
+    pub fn location(&self) -> Result<Location, String> {
+        Location::new(self.latitude, self.longitude)
+    }
+
+    /// Narrows the search down, the point itself is matched by the caller
+    pub fn to_document(&self, settings: &SETTINGS) -> Document {
+        let mut filter = match self.in_service {
+            Some(in_service) => ATM::in_service_query(in_service, settings),
+            None => Document::new(),
+        };
+        if let Some(branch) = &self.branch {
+            filter.insert("branch", branch);
+        }
+        filter.insert("active", doc! { "$ne": false });
+        filter
+    }
+}
+
+fn gcd(a: i64, b: i64) -> i64 {
+    match b {
+        0 => a,
+        _ => gcd(b, a % b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::atm::{Location, ATM, CASSETTE};
+
+    fn atm(cassettes: &[(i64, i64)]) -> ATM {
+        let location = Location::new(19.07, 72.87).unwrap();
+        let mut atm = ATM::new(
+            None,
+            "ATM00001".to_string(),
+            "Branch".to_string(),
+            "Address".to_string(),
+            location,
+            "password".to_string(),
+        );
+        atm.cassettes = cassettes
+            .iter()
+            .map(|&(denomination, count)| CASSETTE {
+                denomination,
+                count,
+            })
+            .collect();
+        atm
+    }
+
+    #[test]
+    fn dispenses_exact_combinations() {
+        let atm = atm(&[(500, 1), (200, 3)]);
+        assert!(atm.can_dispense(700));
+        assert!(atm.can_dispense(1_100));
+        // Greedy would take the 500 and get stuck on 100
+        assert!(atm.can_dispense(600));
+    }
+
+    #[test]
+    fn refuses_amounts_no_notes_make_up() {
+        let atm = atm(&[(500, 10), (200, 10)]);
+        assert!(!atm.can_dispense(0));
+        assert!(!atm.can_dispense(-500));
+        assert!(!atm.can_dispense(150)); // Not a multiple of 100
+        assert!(!atm.can_dispense(300)); // Multiple of 100 but no combination
+        assert!(!atm.can_dispense(100));
+    }
+
+    #[test]
+    fn refuses_amounts_beyond_the_loaded_notes() {
+        let atm = atm(&[(500, 2), (200, 1)]);
+        assert!(!atm.can_dispense(1_300)); // More than the 1200 loaded
+        assert!(!atm.can_dispense(400)); // Needs two 200s, one is loaded
+        assert!(!atm.can_dispense(600)); // Needs three 200s
+        assert!(atm.can_dispense(1_200));
+    }
+
+    #[test]
+    fn ignores_empty_cassettes() {
+        assert!(!atm(&[]).can_dispense(500));
+        assert!(!atm(&[(500, 0), (100, 0)]).can_dispense(500));
+        assert!(atm(&[(500, 0), (100, 5)]).can_dispense(500));
     }
 }
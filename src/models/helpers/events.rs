@@ -0,0 +1,36 @@
+use super::common::timestamp_millis;
+use crate::models::{
+    events::TxnEvent,
+    transaction::{TxnStatus, TRANSACTION},
+};
+
+impl TxnEvent {
+    // Events kept for subscribers that fall behind, older ones are dropped
+    pub const CAPACITY: usize = 1024;
+    pub const NAME: &str = "status";
+    // Seconds between two checks that the token of a subscriber still stands
+    pub const SESSION_CHECK: u64 = 30;
+
+    pub fn new(txn: &TRANSACTION, status: TxnStatus) -> Self {
+        Self {
+            id: txn.id.map(|id| id.to_hex()),
+            account: txn.account.clone(),
+            atm: txn.atm.clone(),
+            destination: txn.destination.clone(),
+            txn_type: txn.txn_type,
+            status,
+            amount: txn.amount,
+            timestamp: timestamp_millis(),
+        }
+    }
+
+    pub fn concerns(&self, field: &str, value: &str) -> bool {
+        let value = Some(value);
+        match field {
+            // Accounts also see the transfers paid into them
+            "account" => self.account.as_deref() == value || self.destination.as_deref() == value,
+            "atm" => self.atm.as_deref() == value,
+            _ => false,
+        }
+    }
+}
@@ -0,0 +1,135 @@
+use crate::models::{
+    fraud::{Decision, FraudRule, History, ASSESSMENT, SIGNAL},
+    settings::SETTINGS,
+};
+
+impl ToString for Decision {
+    fn to_string(&self) -> String {
+        match self {
+            Decision::ALLOW => "ALLOW".to_string(),
+            Decision::CONFIRM => "CONFIRM".to_string(),
+            Decision::BLOCK => "BLOCK".to_string(),
+        }
+    }
+}
+
+impl ToString for FraudRule {
+    fn to_string(&self) -> String {
+        match self {
+            FraudRule::VELOCITY => "VELOCITY".to_string(),
+            FraudRule::AMOUNT => "AMOUNT".to_string(),
+            FraudRule::TRAVEL => "TRAVEL".to_string(),
+            FraudRule::UNSEEN => "UNSEEN".to_string(),
+        }
+    }
+}
+
+impl FraudRule {
+    pub const VELOCITY_WINDOW: i64 = 600_000; // Millis
+    pub const VELOCITY_LIMIT: u64 = 3;
+    pub const VELOCITY_SCORE: i32 = 40;
+    pub const HISTORY_SIZE: i64 = 50; // Completed transactions compared against
+    pub const MIN_HISTORY: usize = 3; // Fewer than this says nothing about the account
+    pub const AMOUNT_FACTOR: i64 = 3; // Times the average amount
+    pub const AMOUNT_SCORE: i32 = 35;
+    pub const MAX_TRAVEL_SPEED: f64 = 250.0; // Meters per second, about an airliner
+    pub const MIN_TRAVEL_DISTANCE: f64 = 50_000.0; // Meters, nearby ATMs are never suspicious
+    pub const TRAVEL_SCORE: i32 = 60;
+    pub const UNSEEN_SCORE: i32 = 15;
+
+    fn signal(self, score: i32, detail: String) -> SIGNAL {
+        SIGNAL {
+            rule: self,
+            score,
+            detail,
+        }
+    }
+
+    pub fn velocity(history: &History) -> Option<SIGNAL> {
+        if history.recent < Self::VELOCITY_LIMIT {
+            return None;
+        }
+        Some(FraudRule::VELOCITY.signal(
+            Self::VELOCITY_SCORE,
+            format!(
+                "{} transactions in the last {} minutes",
+                history.recent,
+                Self::VELOCITY_WINDOW / 60_000
+            ),
+        ))
+    }
+
+    pub fn amount(history: &History, amount: i64) -> Option<SIGNAL> {
+        if history.amounts.len() < Self::MIN_HISTORY {
+            return None;
+        }
+        let total: i64 = history.amounts.iter().sum();
+        let average = total / history.amounts.len() as i64;
+        let largest = history.amounts.iter().max().copied().unwrap_or_default();
+        if amount <= average.saturating_mul(Self::AMOUNT_FACTOR) || amount <= largest {
+            return None;
+        }
+        Some(FraudRule::AMOUNT.signal(
+            Self::AMOUNT_SCORE,
+            format!("{amount} against an average of {average}"),
+        ))
+    }
+
+    pub fn travel(history: &History) -> Option<SIGNAL> {
+        let (distance, elapsed) = history.travel?;
+        if distance < Self::MIN_TRAVEL_DISTANCE {
+            return None;
+        }
+        let seconds = (elapsed.max(1) as f64) / 1000.0;
+        if distance / seconds <= Self::MAX_TRAVEL_SPEED {
+            return None;
+        }
+        Some(FraudRule::TRAVEL.signal(
+            Self::TRAVEL_SCORE,
+            format!(
+                "{:.0} km from the last ATM in {:.0} minutes",
+                distance / 1000.0,
+                seconds / 60.0
+            ),
+        ))
+    }
+
+    pub fn unseen(history: &History) -> Option<SIGNAL> {
+        if history.previous_uses > 0 {
+            return None;
+        }
+        Some(FraudRule::UNSEEN.signal(Self::UNSEEN_SCORE, "First use of the ATM".to_string()))
+    }
+}
+
+impl ASSESSMENT {
+    /// Runs every rule, a transaction that needs confirming is blocked when the challenge cannot be delivered
+    pub fn evaluate(history: &History, amount: i64, reachable: bool, settings: &SETTINGS) -> Self {
+        let signals: Vec<SIGNAL> = [
+            FraudRule::velocity(history),
+            FraudRule::amount(history, amount),
+            FraudRule::travel(history),
+            FraudRule::unseen(history),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        let score = signals.iter().map(|signal| signal.score).sum();
+
+        let decision = if score >= settings.fraud_block_score {
+            Decision::BLOCK
+        } else if score >= settings.fraud_confirm_score {
+            match reachable {
+                true => Decision::CONFIRM,
+                false => Decision::BLOCK,
+            }
+        } else {
+            Decision::ALLOW
+        };
+        Self {
+            score,
+            decision,
+            signals,
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use crate::{
+    models::{
+        handlers::Response,
+        helpers::common::timestamp_millis,
+        idempotency::{IdempotencyKey, IDEMPOTENCY},
+    },
+    utilities::crypto::sign,
+};
+use rocket::{
+    async_trait,
+    http::Status,
+    request::{FromRequest, Outcome},
+    serde::json::to_string,
+};
+use serde::Serialize;
+
+impl IDEMPOTENCY {
+    /// Keyed so the stored value gives nothing away about a PIN or OTP in the body
+    pub fn fingerprint<T: Serialize>(body: &T, secret: &[u8]) -> String {
+        sign(secret, to_string(body).unwrap_or_default().as_bytes())
+    }
+
+    /// A body sent with the same key has to match the first one
+    pub fn matches(&self, fingerprint: &str) -> bool {
+        self.fingerprint.is_empty() || self.fingerprint == fingerprint
+    }
+
+    /// The stored outcome of the first request, None while it is still running
+    pub fn result(&self) -> Option<Result<Response<String>, Status>> {
+        if let Some(response) = &self.response {
+            return Some(Ok(response.clone()));
+        }
+        self.status
+            .map(|code| Err(Status::from_code(code).unwrap_or(Status::InternalServerError)))
+    }
+
+    /// Unfinished for longer than any request runs, the process holding the key is gone
+    pub fn is_abandoned(&self) -> bool {
+        self.result().is_none() && self.started + Self::ABANDONED_AFTER < timestamp_millis()
+    }
+}
+
+#[async_trait]
+impl<'r> FromRequest<'r> for IdempotencyKey {
+    type Error = String;
+
+    async fn from_request(request: &'r rocket::Request<'_>) -> Outcome<Self, Self::Error> {
+        match request.headers().get_one(IDEMPOTENCY::HEADER) {
+            Some(key) if key.is_empty() || key.len() > IDEMPOTENCY::MAX_KEY_LENGTH => {
+                Outcome::Failure((Status::BadRequest, "Invalid Idempotency Key".to_string()))
+            }
+            Some(key) => Outcome::Success(IdempotencyKey(Some(key.to_string()))),
+            None => Outcome::Success(IdempotencyKey(None)),
+        }
+    }
+}
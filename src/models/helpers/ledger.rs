@@ -0,0 +1,99 @@
+use super::common::timestamp_millis;
+use crate::models::{
+    ledger::{Book, Side, ENTRY},
+    transaction::{TxnType, TRANSACTION},
+    user::PAYOUT,
+};
+use mongodb::bson::{oid::ObjectId, DateTime};
+
+impl Book {
+    pub fn value(&self) -> String {
+        match self {
+            Book::ACCOUNT => "ACCOUNT".to_string(),
+            Book::ATM => "ATM".to_string(),
+            Book::PAYOUT => "PAYOUT".to_string(),
+            Book::OPENING => "OPENING".to_string(),
+        }
+    }
+}
+
+impl Side {
+    pub fn value(&self) -> String {
+        match self {
+            Side::DEBIT => "DEBIT".to_string(),
+            Side::CREDIT => "CREDIT".to_string(),
+        }
+    }
+}
+
+impl ENTRY {
+    pub const OPENING_NAME: &str = "OPENING BALANCE";
+
+    pub fn new(txn: ObjectId, book: Book, name: &str, side: Side, amount: i64) -> Self {
+        Self {
+            id: None,
+            txn,
+            book,
+            name: name.to_string(),
+            side,
+            amount,
+            created: DateTime::from_millis(timestamp_millis()),
+        }
+    }
+
+    /// The balanced pair taking the paid out balance off a closing account
+    pub fn for_payout(id: ObjectId, number: &str, payout: &PAYOUT) -> Vec<ENTRY> {
+        let method = payout.method.to_string();
+        vec![
+            ENTRY::new(id, Book::ACCOUNT, number, Side::DEBIT, payout.amount),
+            ENTRY::new(id, Book::PAYOUT, &method, Side::CREDIT, payout.amount),
+        ]
+    }
+
+    /// The balanced pair bringing an account that predates the ledger up to its balance
+    pub fn for_opening(id: ObjectId, number: &str, amount: i64) -> Vec<ENTRY> {
+        let (account, opening) = match amount < 0 {
+            true => (Side::DEBIT, Side::CREDIT),
+            false => (Side::CREDIT, Side::DEBIT),
+        };
+        let amount = amount.abs();
+        vec![
+            ENTRY::new(id, Book::OPENING, Self::OPENING_NAME, opening, amount),
+            ENTRY::new(id, Book::ACCOUNT, number, account, amount),
+        ]
+    }
+
+    /// The balanced debit and credit pair for a transaction stored under the given id
+    pub fn for_txn(id: ObjectId, txn: &TRANSACTION) -> Result<Vec<ENTRY>, String> {
+        let account = match &txn.account {
+            Some(account) => account,
+            None => return Err("Account Missing".to_string()),
+        };
+        let counterpart = match txn.txn_type {
+            TxnType::TRANSFER => txn.destination.as_ref(),
+            _ => txn.atm.as_ref(),
+        };
+        let counterpart = match counterpart {
+            Some(counterpart) => counterpart,
+            None => return Err("Counterpart Missing".to_string()),
+        };
+
+        let amount = txn.amount;
+        Ok(match txn.txn_type {
+            // Customer takes cash out of the machine
+            TxnType::DEBIT => vec![
+                ENTRY::new(id, Book::ACCOUNT, account, Side::DEBIT, amount),
+                ENTRY::new(id, Book::ATM, counterpart, Side::CREDIT, amount),
+            ],
+            // Customer puts cash into the machine
+            TxnType::CREDIT => vec![
+                ENTRY::new(id, Book::ATM, counterpart, Side::DEBIT, amount),
+                ENTRY::new(id, Book::ACCOUNT, account, Side::CREDIT, amount),
+            ],
+            TxnType::TRANSFER => vec![
+                ENTRY::new(id, Book::ACCOUNT, account, Side::DEBIT, amount),
+                ENTRY::new(id, Book::ACCOUNT, counterpart, Side::CREDIT, amount),
+            ],
+        })
+    }
+}
@@ -0,0 +1,93 @@
+use super::common::timestamp_millis;
+use crate::models::{
+    notification::{Channel, Notice, CONTACT, NOTIFICATION},
+    transaction::TxnStatus,
+};
+
+impl ToString for Channel {
+    fn to_string(&self) -> String {
+        match self {
+            Channel::EMAIL => "EMAIL".to_string(),
+            Channel::SMS => "SMS".to_string(),
+            Channel::PUSH => "PUSH".to_string(),
+        }
+    }
+}
+
+impl Notice {
+    /// Notice sent for a status, None for statuses the account holder is not told about
+    pub fn from_status(status: TxnStatus) -> Option<Self> {
+        match status {
+            TxnStatus::PENDING => Some(Notice::TxnCreated),
+            TxnStatus::COMPLETE => Some(Notice::TxnCompleted),
+            TxnStatus::REJECTED => Some(Notice::TxnRejected),
+            TxnStatus::EXPIRED | TxnStatus::REVERSED => None,
+        }
+    }
+
+    pub fn subject(&self) -> &'static str {
+        match self {
+            Notice::TxnCreated => "Confirm your transaction",
+            Notice::TxnChallenge => "Verify your transaction",
+            Notice::TxnCompleted => "Transaction completed",
+            Notice::TxnRejected => "Transaction rejected",
+            Notice::TxnReceived => "Transfer received",
+            Notice::TxnReversed => "Transfer reversed",
+            Notice::NewDevice => "New login to your account",
+        }
+    }
+}
+
+impl CONTACT {
+    pub const MAX_LENGTH: usize = 255;
+
+    pub fn validate(&self) -> Result<&Self, String> {
+        if let Some(email) = &self.email {
+            let valid = match email.split_once('@') {
+                Some((user, domain)) => !user.is_empty() && domain.contains('.'),
+                None => false,
+            };
+            if !valid || email.len() > Self::MAX_LENGTH {
+                return Err("Invalid email address".to_string());
+            }
+        }
+        if let Some(phone) = &self.phone {
+            let digits = phone.strip_prefix('+').unwrap_or(phone);
+            if digits.len() < 7 || digits.len() > 15 || !digits.chars().all(|c| c.is_ascii_digit())
+            {
+                return Err("Phone must be 7 to 15 digits with an optional +".to_string());
+            }
+        }
+        if let Some(token) = &self.push_token {
+            if token.is_empty() || token.len() > Self::MAX_LENGTH {
+                return Err("Invalid push token".to_string());
+            }
+        }
+        Ok(self)
+    }
+
+    /// Every channel the account can be reached on, with the recipient to use
+    pub fn recipients(&self) -> Vec<(Channel, String)> {
+        [
+            (Channel::EMAIL, &self.email),
+            (Channel::SMS, &self.phone),
+            (Channel::PUSH, &self.push_token),
+        ]
+        .into_iter()
+        .filter_map(|(channel, recipient)| recipient.clone().map(|value| (channel, value)))
+        .collect()
+    }
+}
+
+impl NOTIFICATION {
+    pub fn new(channel: Channel, recipient: String, notice: Notice, body: String) -> Self {
+        Self {
+            channel,
+            recipient,
+            notice,
+            subject: notice.subject().to_string(),
+            body,
+            timestamp: timestamp_millis(),
+        }
+    }
+}
@@ -0,0 +1,185 @@
+use crate::{
+    database::repository::Repository,
+    models::{
+        admin::Role,
+        permission::{Permission, Permitted, Required, PERMISSIONS},
+        token::{Type, TOKEN},
+    },
+};
+use rocket::{
+    async_trait,
+    http::Status,
+    request::{FromRequest, Outcome},
+    State,
+};
+use std::{collections::HashSet, env, marker::PhantomData, str::FromStr};
+
+impl Permission {
+    pub const ALL: [Permission; 18] = [
+        Permission::AdminCreate,
+        Permission::AdminRead,
+        Permission::AdminManage,
+        Permission::AccountCreate,
+        Permission::AccountRead,
+        Permission::AccountPin,
+        Permission::AccountStatus,
+        Permission::AccountClose,
+        Permission::AtmCreate,
+        Permission::AtmRead,
+        Permission::AtmUpdate,
+        Permission::AtmService,
+        Permission::AtmCassettes,
+        Permission::AtmDelete,
+        Permission::TxnRead,
+        Permission::TxnReverse,
+        Permission::LedgerReconcile,
+        Permission::WebhookManage,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Permission::AdminCreate => "admin:create",
+            Permission::AdminRead => "admin:read",
+            Permission::AdminManage => "admin:manage",
+            Permission::AccountCreate => "account:create",
+            Permission::AccountRead => "account:read",
+            Permission::AccountPin => "account:pin",
+            Permission::AccountStatus => "account:status",
+            Permission::AccountClose => "account:close",
+            Permission::AtmCreate => "atm:create",
+            Permission::AtmRead => "atm:read",
+            Permission::AtmUpdate => "atm:update",
+            Permission::AtmService => "atm:service",
+            Permission::AtmCassettes => "atm:cassettes",
+            Permission::AtmDelete => "atm:delete",
+            Permission::TxnRead => "txn:read",
+            Permission::TxnReverse => "txn:reverse",
+            Permission::LedgerReconcile => "ledger:reconcile",
+            Permission::WebhookManage => "webhook:manage",
+        }
+    }
+
+    /// Lowest role holding the permission when the role is not configured
+    pub fn default_role(&self) -> Role {
+        match self {
+            Permission::AccountPin
+            | Permission::AccountStatus
+            | Permission::AtmCreate
+            | Permission::AtmUpdate
+            | Permission::AtmService
+            | Permission::AtmCassettes
+            | Permission::TxnReverse => Role::SUPERVISOR,
+            Permission::AccountClose
+            | Permission::AtmDelete
+            | Permission::LedgerReconcile
+            | Permission::WebhookManage => Role::MANAGER,
+            _ => Role::STAFF,
+        }
+    }
+}
+
+impl FromStr for Permission {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.trim().to_lowercase();
+        match Permission::ALL
+            .into_iter()
+            .find(|permission| permission.name() == name)
+        {
+            Some(permission) => Ok(permission),
+            None => Err(format!("Unknown permission {name}")),
+        }
+    }
+}
+
+impl PERMISSIONS {
+    pub const ROLES: [Role; 6] = [
+        Role::STAFF,
+        Role::COORIDNATOR,
+        Role::SUPERVISOR,
+        Role::MANAGER,
+        Role::EXECUTIVE,
+        Role::MANAGEMENT,
+    ];
+
+    /// Reads PERMISSIONS_<ROLE> as a comma separated list, roles left out keep their defaults
+    pub fn load() -> Self {
+        let mut permissions = Self::default();
+        for role in Self::ROLES {
+            let granted = match env::var(format!("PERMISSIONS_{}", role.to_string())) {
+                Ok(value) => value
+                    .split(',')
+                    .filter(|name| !name.trim().is_empty())
+                    .filter_map(|name| match Permission::from_str(name) {
+                        Ok(permission) => Some(permission),
+                        Err(error) => {
+                            println!("{error} for {}, ignored", role.to_string());
+                            None
+                        }
+                    })
+                    .collect(),
+                Err(_) => Self::defaults(role),
+            };
+            permissions.roles.insert(role.value(), granted);
+        }
+        permissions
+    }
+
+    pub fn defaults(role: Role) -> HashSet<Permission> {
+        Permission::ALL
+            .into_iter()
+            .filter(|permission| role >= permission.default_role())
+            .collect()
+    }
+
+    pub fn allows(&self, role: Option<Role>, permission: Permission) -> bool {
+        match role.and_then(|role| self.roles.get(&role.value())) {
+            Some(granted) => granted.contains(&permission),
+            None => false,
+        }
+    }
+}
+
+#[async_trait]
+impl<'r, P: Required> FromRequest<'r> for Permitted<P> {
+    type Error = String;
+
+    async fn from_request(request: &'r rocket::Request<'_>) -> Outcome<Self, Self::Error> {
+        let token = match request.guard::<TOKEN>().await {
+            Outcome::Success(token) => token,
+            Outcome::Failure(failure) => return Outcome::Failure(failure),
+            Outcome::Forward(forward) => return Outcome::Forward(forward),
+        };
+        let unauthorized =
+            || Outcome::Failure((Status::Unauthorized, TOKEN::UNAUTHORIZED_ERROR.to_string()));
+        if !Type::ADMIN.cmp(&token.role.value()) {
+            return unauthorized();
+        }
+
+        let db = match request.guard::<&State<Repository>>().await {
+            Outcome::Success(db) => db,
+            _ => {
+                return Outcome::Failure((
+                    Status::InternalServerError,
+                    TOKEN::INTERNAL_SERVER_ERROR.to_string(),
+                ))
+            }
+        };
+        let admin = match db.get_admin_from_id(&token.sub).await {
+            Ok(admin) => admin,
+            Err(_) => return unauthorized(),
+        };
+        let id = match admin.id {
+            Some(id) => id,
+            None => return unauthorized(),
+        };
+        if !db.permissions.allows(admin.role, P::PERMISSION) {
+            return unauthorized();
+        }
+        Outcome::Success(Self {
+            admin,
+            id,
+            permission: PhantomData,
+        })
+    }
+}
@@ -0,0 +1,60 @@
+use super::common::timestamp_millis;
+use crate::models::{settings::SETTINGS, token::TOKEN};
+use std::{env, str::FromStr};
+
+fn read_var<T: FromStr>(name: &str, default: T) -> T {
+    match env::var(name) {
+        Ok(value) => match value.parse() {
+            Ok(value) => value,
+            Err(_) => {
+                println!("Invalid value for {name}, using the default");
+                default
+            }
+        },
+        Err(_) => default,
+    }
+}
+
+impl SETTINGS {
+    /// Loads the settings from the environment, every variable is optional
+    pub fn load() -> Self {
+        let defaults = Self::default();
+        Self {
+            max_otp_attempts: read_var("MAX_OTP_ATTEMPTS", defaults.max_otp_attempts),
+            max_lockouts: read_var("MAX_LOCKOUTS", defaults.max_lockouts),
+            lockout_duration: read_var("LOCKOUT_DURATION", defaults.lockout_duration),
+            txn_limit: read_var("TXN_LIMIT", defaults.txn_limit),
+            daily_limit: read_var("DAILY_LIMIT", defaults.daily_limit),
+            atm_daily_limit: read_var("ATM_DAILY_LIMIT", defaults.atm_daily_limit),
+            business_day_offset: read_var("BUSINESS_DAY_OFFSET", defaults.business_day_offset),
+            totp_digits: read_var("TOTP_DIGITS", defaults.totp_digits).clamp(6, 8),
+            totp_step: read_var("TOTP_STEP", defaults.totp_step).max(1),
+            totp_skew: read_var("TOTP_SKEW", defaults.totp_skew),
+            webhook_max_attempts: read_var("WEBHOOK_MAX_ATTEMPTS", defaults.webhook_max_attempts),
+            webhook_backoff: read_var("WEBHOOK_BACKOFF", defaults.webhook_backoff).max(1),
+            fraud_confirm_score: read_var("FRAUD_CONFIRM_SCORE", defaults.fraud_confirm_score),
+            fraud_block_score: read_var("FRAUD_BLOCK_SCORE", defaults.fraud_block_score),
+            max_atm_distance: read_var("MAX_ATM_DISTANCE", defaults.max_atm_distance).max(0.0),
+            heartbeat_timeout: read_var("HEARTBEAT_TIMEOUT", defaults.heartbeat_timeout).max(1),
+            low_cash_level: read_var("LOW_CASH_LEVEL", defaults.low_cash_level),
+            access_token_expiry: read_var("ACCESS_TOKEN_EXPIRY", defaults.access_token_expiry)
+                .clamp(1, TOKEN::DEFAULT_EXPIRY),
+            refresh_token_expiry: read_var("REFRESH_TOKEN_EXPIRY", defaults.refresh_token_expiry)
+                .max(1),
+        }
+    }
+
+    /// Millis to wait before retrying a delivery that failed the given number of times
+    pub fn webhook_delay(&self, attempts: i32) -> i64 {
+        let exponent = (attempts - 1).clamp(0, 30) as u32;
+        self.webhook_backoff
+            .saturating_mul(1i64 << exponent)
+            .min(Self::MAX_WEBHOOK_BACKOFF)
+    }
+
+    /// Millis at which the current business day started
+    pub fn business_day_start(&self) -> i64 {
+        let now = timestamp_millis() - self.business_day_offset;
+        now - now.rem_euclid(Self::DAY) + self.business_day_offset
+    }
+}
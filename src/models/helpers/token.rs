@@ -36,6 +36,15 @@ impl Type {
             Err(_) => false,
         }
     }
+
+    /// Role the principal is recorded under in the logs
+    pub fn log_role(&self) -> &'static str {
+        match self {
+            Type::ADMIN => "Admin",
+            Type::ACCOUNT => "User",
+            Type::ATM => "ATM",
+        }
+    }
 }
 
 impl FromStr for Type {
@@ -188,6 +197,12 @@ impl<'r> FromRequest<'r> for TOKEN {
                                 return Outcome::Failure((error, Self::NOT_FOUND.to_string()))
                             }
                         };
+                        if !account.is_active() {
+                            return Outcome::Failure((
+                                Status::Forbidden,
+                                Self::INACTIVE.to_string(),
+                            ));
+                        }
                         let id = option!(val -> account.token; {val} | {return Outcome::Failure((Status::NotFound, Self::NOT_FOUND.to_string()))});
                         id
                     }
@@ -198,6 +213,12 @@ impl<'r> FromRequest<'r> for TOKEN {
                                 return Outcome::Failure((error, Self::NOT_FOUND.to_string()))
                             }
                         };
+                        if !admin.active {
+                            return Outcome::Failure((
+                                Status::Forbidden,
+                                Self::INACTIVE.to_string(),
+                            ));
+                        }
                         let id = option!(val -> admin.token; {val} | {return Outcome::Failure((Status::NotFound, Self::NOT_FOUND.to_string()))});
                         id
                     }
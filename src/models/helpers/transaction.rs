@@ -1,5 +1,12 @@
-use super::common::timestamp;
-use crate::models::transaction::{TxnStatus, TxnType, TRANSACTION};
+use super::common::timestamp_millis;
+use crate::{
+    models::transaction::{
+        TxnFilter, TxnLimit, TxnStatus, TxnType, TRANSACTION, TRANSITION, TXN_TRANSITIONS,
+    },
+    utilities::crypto::{hash_password_default, verify_password, Generator},
+};
+use mongodb::bson::{doc, oid::ObjectId, DateTime, Document};
+use rocket::http::Status;
 use std::str::FromStr;
 
 impl TxnType {
@@ -7,6 +14,7 @@ impl TxnType {
         match self {
             TxnType::DEBIT => "DEBIT".to_string(),
             TxnType::CREDIT => "CREDIT".to_string(),
+            TxnType::TRANSFER => "TRANSFER".to_string(),
         }
     }
 
@@ -29,6 +37,7 @@ impl FromStr for TxnType {
         match s.to_uppercase().as_str() {
             "DEBIT" => Ok(TxnType::DEBIT),
             "CREDIT" => Ok(TxnType::CREDIT),
+            "TRANSFER" => Ok(TxnType::TRANSFER),
             _ => Err("Invalid Value".to_string()),
         }
     }
@@ -39,6 +48,7 @@ impl ToString for TxnType {
         match self {
             TxnType::DEBIT => "DEBIT".to_string(),
             TxnType::CREDIT => "CREDIT".to_string(),
+            TxnType::TRANSFER => "TRANSFER".to_string(),
         }
     }
 }
@@ -48,6 +58,7 @@ impl PartialOrd for TxnType {
         match &self {
             TxnType::DEBIT => Some(std::cmp::Ordering::Equal),
             TxnType::CREDIT => Some(std::cmp::Ordering::Equal),
+            TxnType::TRANSFER => Some(std::cmp::Ordering::Equal),
         }
     }
 
@@ -75,6 +86,7 @@ impl TxnStatus {
             TxnStatus::EXPIRED => "EXPIRED".to_string(),
             TxnStatus::COMPLETE => "COMPLETE".to_string(),
             TxnStatus::REJECTED => "REJECTED".to_string(),
+            TxnStatus::REVERSED => "REVERSED".to_string(),
         }
     }
 
@@ -89,6 +101,10 @@ impl TxnStatus {
             Err(_) => false,
         }
     }
+
+    pub fn can_transition(&self, to: TxnStatus) -> bool {
+        TXN_TRANSITIONS.contains(&(*self, to))
+    }
 }
 
 impl FromStr for TxnStatus {
@@ -99,6 +115,7 @@ impl FromStr for TxnStatus {
             "EXPIRED" => Ok(TxnStatus::EXPIRED),
             "COMPLETE" => Ok(TxnStatus::COMPLETE),
             "REJECTED" => Ok(TxnStatus::REJECTED),
+            "REVERSED" => Ok(TxnStatus::REVERSED),
             _ => Err("Invalid Value".to_string()),
         }
     }
@@ -111,20 +128,224 @@ impl ToString for TxnStatus {
             TxnStatus::EXPIRED => "EXPIRED".to_string(),
             TxnStatus::COMPLETE => "COMPLETE".to_string(),
             TxnStatus::REJECTED => "REJECTED".to_string(),
+            TxnStatus::REVERSED => "REVERSED".to_string(),
+        }
+    }
+}
+
+impl TxnLimit {
+    pub fn value(&self) -> String {
+        match self {
+            TxnLimit::TRANSACTION => "Transaction Limit Exceeded".to_string(),
+            TxnLimit::DAILY => "Daily Limit Exceeded".to_string(),
+            TxnLimit::ATM => "ATM Daily Limit Exceeded".to_string(),
         }
     }
 }
 
 impl TRANSACTION {
+    /// Builds the COMPLETE transaction that moves the money of this one back
+    pub fn reversal(&self) -> Self {
+        let (txn_type, account, destination) = match self.txn_type {
+            TxnType::DEBIT => (TxnType::CREDIT, self.account.clone(), None),
+            TxnType::CREDIT => (TxnType::DEBIT, self.account.clone(), None),
+            TxnType::TRANSFER => (
+                TxnType::TRANSFER,
+                self.destination.clone(),
+                self.account.clone(),
+            ),
+        };
+        Self {
+            id: None,
+            account,
+            atm: self.atm.clone(),
+            destination,
+            amount: self.amount,
+            txn_type,
+            status: TxnStatus::COMPLETE,
+            attempts: 0,
+            reversal_of: self.id,
+            reversed_by: None,
+            created: DateTime::from_millis(timestamp_millis()),
+            transitions: Vec::new(),
+            coordinates: None,
+            risk: None,
+            challenge: None,
+        }
+    }
+
+    /// Short human readable summary used in notifications
+    pub fn describe(&self) -> String {
+        let place = match (&self.atm, &self.destination) {
+            (_, Some(destination)) => format!(" to account {destination}"),
+            (Some(atm), None) => format!(" at ATM {atm}"),
+            (None, None) => String::new(),
+        };
+        let kind = match self.txn_type {
+            TxnType::DEBIT => "Withdrawal",
+            TxnType::CREDIT => "Deposit",
+            TxnType::TRANSFER => "Transfer",
+        };
+        format!("{kind} of {}{place}", self.amount)
+    }
+
+    /// Stores a hashed challenge and returns it readable, the only time it is
+    pub fn generate_challenge(&mut self) -> Result<String, Status> {
+        let challenge = Generator::generate_number_i32(100_000, 999_999).to_string();
+        self.challenge = match hash_password_default(challenge.to_owned()) {
+            Ok(hash) => Some(hash),
+            Err(error) => {
+                println!("Error while hashing: {error}");
+                return Err(Status::InternalServerError);
+            }
+        };
+        Ok(challenge)
+    }
+
+    /// Transactions without a challenge pass whatever is sent
+    pub fn verify_challenge(&self, challenge: Option<&str>) -> Result<bool, Status> {
+        let stored = match &self.challenge {
+            Some(stored) => stored,
+            None => return Ok(true),
+        };
+        let challenge = match challenge {
+            Some(challenge) => challenge.to_string(),
+            None => return Ok(false),
+        };
+        match verify_password(&challenge, stored) {
+            Ok(result) => Ok(result),
+            Err(error) => {
+                println!("Error: {error}");
+                Err(Status::InternalServerError)
+            }
+        }
+    }
+
     pub fn is_valid(&mut self, balance: i64) -> bool {
-        if self.created.timestamp_millis() + Self::DEFAULT_EXPIRY < timestamp() {
+        if self.created.timestamp_millis() + Self::DEFAULT_EXPIRY < timestamp_millis() {
             self.status = TxnStatus::EXPIRED;
             return false;
         }
-        if self.txn_type == TxnType::DEBIT && balance < self.amount {
+        if self.txn_type != TxnType::CREDIT && balance < self.amount {
             self.status = TxnStatus::REJECTED;
             return false;
         }
         true
     }
 }
+
+impl TRANSITION {
+    pub fn new(
+        from: Option<TxnStatus>,
+        to: TxnStatus,
+        actor: Option<ObjectId>,
+        role: &str,
+    ) -> Self {
+        Self {
+            from,
+            to,
+            actor,
+            role: role.to_string(),
+            timestamp: timestamp_millis(),
+        }
+    }
+}
+
+impl TxnFilter {
+    pub const DEFAULT_LIMIT: i64 = 20;
+    pub const MAX_LIMIT: i64 = 100;
+
+    pub fn limit(&self) -> i64 {
+        match self.limit {
+            Some(limit) if limit > 0 => limit.min(Self::MAX_LIMIT),
+            _ => Self::DEFAULT_LIMIT,
+        }
+    }
+
+    /// Builds the query for one page, account and atm are to be set by the caller
+    pub fn to_document(&self) -> Result<Document, String> {
+        let mut filter = Document::new();
+
+        // Transfers the account received are part of its history too
+        if let Some(account) = &self.account {
+            filter.insert(
+                "$or",
+                vec![doc! { "account": account }, doc! { "destination": account }],
+            );
+        }
+        if let Some(atm) = &self.atm {
+            filter.insert("atm", atm);
+        }
+        if let Some(status) = &self.status {
+            filter.insert("status", TxnStatus::from_str(status)?.to_string());
+        }
+        if let Some(txn_type) = &self.txn_type {
+            filter.insert("txn_type", TxnType::from_str(txn_type)?.to_string());
+        }
+
+        let mut created = Document::new();
+        if let Some(from) = self.from {
+            created.insert("$gte", DateTime::from_millis(from));
+        }
+        if let Some(to) = self.to {
+            created.insert("$lte", DateTime::from_millis(to));
+        }
+        if !created.is_empty() {
+            filter.insert("created", created);
+        }
+
+        let mut amount = Document::new();
+        if let Some(min) = self.min {
+            amount.insert("$gte", min);
+        }
+        if let Some(max) = self.max {
+            amount.insert("$lte", max);
+        }
+        if !amount.is_empty() {
+            filter.insert("amount", amount);
+        }
+
+        if let Some(cursor) = &self.cursor {
+            let cursor = match ObjectId::parse_str(cursor) {
+                Ok(cursor) => cursor,
+                Err(_) => return Err("Invalid Cursor".to_string()),
+            };
+            filter.insert("_id", doc! { "$lt": cursor });
+        }
+
+        Ok(filter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::transaction::TxnStatus::{COMPLETE, EXPIRED, PENDING, REJECTED, REVERSED};
+
+    #[test]
+    fn pending_settles_rejects_or_expires() {
+        assert!(PENDING.can_transition(COMPLETE));
+        assert!(PENDING.can_transition(REJECTED));
+        assert!(PENDING.can_transition(EXPIRED));
+        assert!(!PENDING.can_transition(PENDING));
+        assert!(!PENDING.can_transition(REVERSED));
+    }
+
+    #[test]
+    fn complete_can_only_be_reversed() {
+        assert!(COMPLETE.can_transition(REVERSED));
+        assert!(!COMPLETE.can_transition(COMPLETE));
+        assert!(!COMPLETE.can_transition(PENDING));
+        assert!(!COMPLETE.can_transition(REJECTED));
+        assert!(!COMPLETE.can_transition(EXPIRED));
+    }
+
+    #[test]
+    fn final_statuses_never_settle() {
+        assert!(!EXPIRED.can_transition(COMPLETE));
+        assert!(!REJECTED.can_transition(COMPLETE));
+        assert!(!REVERSED.can_transition(COMPLETE));
+        assert!(!EXPIRED.can_transition(PENDING));
+        assert!(!REJECTED.can_transition(PENDING));
+        assert!(!REVERSED.can_transition(REVERSED));
+    }
+}
@@ -1,10 +1,185 @@
-use crate::models::user::ACCOUNT;
+use super::common::timestamp_millis;
+use crate::{
+    models::{
+        keys::KEY,
+        settings::SETTINGS,
+        user::{AccountStatus, Device, PayoutMethod, ACCOUNT, PAYOUT},
+    },
+    utilities::crypto::{decrypt, from_hex, hasher, to_base32, verify_password},
+};
+use ring::constant_time::verify_slices_are_equal;
+use rocket::{
+    async_trait,
+    http::Status,
+    request::{FromRequest, Outcome},
+};
 
 impl ACCOUNT {
+    pub const TOTP_ISSUER: &str = "TouchlessATM"; // Label shown by authenticator apps
+
     pub fn validate(&self) -> Result<&Self, String> {
         if self.password.len() < 8 {
             return Err("Password too short".to_string());
         }
+        if let Some(contact) = &self.contact {
+            contact.validate()?;
+        }
+        Ok(self)
+    }
+
+    pub const MIN_PIN_LENGTH: usize = 4;
+    pub const MAX_PIN_LENGTH: usize = 6;
+
+    pub fn validate_pin(pin: &str) -> Result<(), String> {
+        if pin.len() < Self::MIN_PIN_LENGTH || pin.len() > Self::MAX_PIN_LENGTH {
+            return Err("PIN must be 4 to 6 digits".to_string());
+        }
+        if !pin.chars().all(|digit| digit.is_ascii_digit()) {
+            return Err("PIN must only contain digits".to_string());
+        }
+        Ok(())
+    }
+
+    // Hashes start with the bcrypt prefix, anything else is a PIN stored before hashing
+    pub fn is_pin_hashed(&self) -> bool {
+        match &self.pin {
+            Some(pin) => pin.starts_with("$2"),
+            None => false,
+        }
+    }
+
+    pub fn verify_pin(&self, pin: &str) -> Result<bool, Status> {
+        let stored = match &self.pin {
+            Some(stored) => stored,
+            None => return Ok(false),
+        };
+        if !self.is_pin_hashed() {
+            return Ok(verify_slices_are_equal(stored.as_bytes(), pin.as_bytes()).is_ok());
+        }
+        match verify_password(&pin.to_string(), stored) {
+            Ok(result) => Ok(result),
+            Err(error) => {
+                println!("Error: {error}");
+                Err(Status::InternalServerError)
+            }
+        }
+    }
+
+    pub fn is_blocked(&self) -> bool {
+        match self.blocked_until {
+            Some(until) => until > timestamp_millis(),
+            None => false,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.status == AccountStatus::ACTIVE
+    }
+
+    /// Active and not blocked after too many failed confirmations
+    pub fn can_transact(&self) -> bool {
+        self.is_active() && !self.is_blocked()
+    }
+
+    /// Dormant accounts still take incoming transfers
+    pub fn can_receive(&self) -> bool {
+        matches!(self.status, AccountStatus::ACTIVE | AccountStatus::DORMANT)
+    }
+
+    pub const MAX_DEVICES: i32 = 20; // Oldest fingerprints are forgotten past this
+
+    pub fn knows_device(&self, device: &Device) -> bool {
+        self.devices.contains(&device.fingerprint)
+    }
+
+    // Throws 404 when the account has no secret yet
+    pub fn totp_secret(&self, keys: &KEY) -> Result<Vec<u8>, Status> {
+        let secret = match &self.totp_secret {
+            Some(secret) => secret.to_owned(),
+            None => return Err(Status::NotFound),
+        };
+        let secret = match decrypt(keys.bytes, secret, &keys.secret, &keys.seed, keys.rate) {
+            Ok(secret) => secret,
+            Err(error) => {
+                println!("Error: {error}");
+                return Err(Status::InternalServerError);
+            }
+        };
+        // Decryption hands back the stored hex string as hex
+        match from_hex(secret).map(String::from_utf8) {
+            Ok(Ok(secret)) => match from_hex(secret) {
+                Ok(secret) => Ok(secret),
+                Err(_) => Err(Status::InternalServerError),
+            },
+            _ => Err(Status::InternalServerError),
+        }
+    }
+
+    // Provisioning uri read by authenticator apps, usually shown as a QR code
+    pub fn totp_uri(&self, secret: &[u8], settings: &SETTINGS) -> String {
+        format!(
+            "otpauth://totp/{issuer}:{number}?secret={secret}&issuer={issuer}&algorithm=SHA1&digits={digits}&period={period}",
+            issuer = Self::TOTP_ISSUER,
+            number = self.number.as_ref().unwrap(),
+            secret = to_base32(secret),
+            digits = settings.totp_digits,
+            period = settings.totp_step,
+        )
+    }
+}
+
+#[async_trait]
+impl<'r> FromRequest<'r> for Device {
+    type Error = String;
+
+    async fn from_request(request: &'r rocket::Request<'_>) -> Outcome<Self, Self::Error> {
+        let agent = request
+            .headers()
+            .get_one("User-Agent")
+            .unwrap_or("Unknown")
+            .to_string();
+        Outcome::Success(Device {
+            fingerprint: hasher(agent.to_owned()),
+            agent,
+        })
+    }
+}
+
+impl ToString for AccountStatus {
+    fn to_string(&self) -> String {
+        match self {
+            AccountStatus::ACTIVE => "ACTIVE".to_string(),
+            AccountStatus::FROZEN => "FROZEN".to_string(),
+            AccountStatus::DORMANT => "DORMANT".to_string(),
+            AccountStatus::CLOSED => "CLOSED".to_string(),
+        }
+    }
+}
+
+impl AccountStatus {
+    /// Closed accounts stay closed, anything else can move to any other status
+    pub fn can_change_to(&self, status: AccountStatus) -> bool {
+        *self != AccountStatus::CLOSED && *self != status
+    }
+}
+
+impl ToString for PayoutMethod {
+    fn to_string(&self) -> String {
+        match self {
+            PayoutMethod::CASH => "CASH".to_string(),
+            PayoutMethod::CHEQUE => "CHEQUE".to_string(),
+            PayoutMethod::TRANSFER => "TRANSFER".to_string(),
+        }
+    }
+}
+
+impl PAYOUT {
+    pub const MAX_REFERENCE_LENGTH: usize = 255;
+
+    pub fn validate(&self) -> Result<&Self, String> {
+        if self.reference.trim().is_empty() || self.reference.len() > Self::MAX_REFERENCE_LENGTH {
+            return Err("Invalid Reference".to_string());
+        }
         Ok(self)
     }
 }
@@ -18,7 +193,17 @@ impl Clone for ACCOUNT {
             number: self.number.to_owned(),
             token: self.token.to_owned(),
             balance: self.balance,
-            pin: self.pin,
+            pin: self.pin.to_owned(),
+            pin_temporary: self.pin_temporary,
+            lockouts: self.lockouts,
+            blocked_until: self.blocked_until,
+            totp_secret: self.totp_secret.to_owned(),
+            totp_counter: self.totp_counter,
+            totp_enrolled: self.totp_enrolled,
+            contact: self.contact.to_owned(),
+            devices: self.devices.to_owned(),
+            status: self.status,
+            payout: self.payout.to_owned(),
         }
     }
 }
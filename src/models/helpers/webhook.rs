@@ -0,0 +1,160 @@
+use super::common::timestamp_millis;
+use crate::{
+    models::{
+        keys::KEY,
+        transaction::{TxnStatus, TRANSACTION},
+        webhook::{DeliveryStatus, WebhookEvent, WEBHOOK},
+    },
+    utilities::crypto::{decrypt, encrypt, from_hex, to_hex, Generator},
+};
+use reqwest::Url;
+use rocket::http::Status;
+use std::net::IpAddr;
+
+impl ToString for DeliveryStatus {
+    fn to_string(&self) -> String {
+        match self {
+            DeliveryStatus::PENDING => "PENDING".to_string(),
+            DeliveryStatus::DELIVERED => "DELIVERED".to_string(),
+            DeliveryStatus::FAILED => "FAILED".to_string(),
+        }
+    }
+}
+
+impl WEBHOOK {
+    pub const SIGNATURE_HEADER: &str = "X-Webhook-Signature";
+    pub const EVENT_HEADER: &str = "X-Webhook-Event";
+    pub const DELIVERY_HEADER: &str = "X-Webhook-Delivery";
+    pub const TIMESTAMP_HEADER: &str = "X-Webhook-Timestamp";
+
+    /// Name of the event sent for a status, None for statuses that are not published
+    pub fn event_name(status: TxnStatus) -> Option<&'static str> {
+        match status {
+            TxnStatus::COMPLETE => Some("transaction.completed"),
+            TxnStatus::REJECTED => Some("transaction.rejected"),
+            TxnStatus::EXPIRED => Some("transaction.expired"),
+            TxnStatus::REVERSED => Some("transaction.reversed"),
+            TxnStatus::PENDING => None,
+        }
+    }
+
+    /// Addresses the dispatcher never posts to, so a webhook cannot reach into the bank's own network
+    pub fn is_internal(ip: IpAddr) -> bool {
+        match ip {
+            IpAddr::V4(ip) => {
+                let [first, second, ..] = ip.octets();
+                ip.is_loopback()
+                    || ip.is_private()
+                    || ip.is_link_local()
+                    || ip.is_unspecified()
+                    || ip.is_broadcast()
+                    || ip.is_documentation()
+                    || (first == 100 && (64..128).contains(&second)) // Shared address space
+            }
+            IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+                Some(ip) => Self::is_internal(IpAddr::V4(ip)),
+                None => {
+                    let first = ip.segments()[0];
+                    ip.is_loopback()
+                        || ip.is_unspecified()
+                        || (first & 0xfe00) == 0xfc00 // Unique local
+                        || (first & 0xffc0) == 0xfe80 // Link-local
+                }
+            },
+        }
+    }
+
+    /// Host and port the url points to, only https is accepted
+    pub fn target(&self) -> Result<(String, u16), String> {
+        let url = match Url::parse(&self.url) {
+            Ok(url) => url,
+            Err(_) => return Err("Invalid url".to_string()),
+        };
+        if url.scheme() != "https" {
+            return Err("Url must be https".to_string());
+        }
+        let host = match url.host_str() {
+            Some(host) => host.trim_start_matches('[').trim_end_matches(']'),
+            None => return Err("Url must have a host".to_string()),
+        };
+        Ok((
+            host.to_lowercase(),
+            url.port_or_known_default().unwrap_or(443),
+        ))
+    }
+
+    pub fn validate(&self) -> Result<&Self, String> {
+        let (host, _) = self.target()?;
+        let internal = match host.parse::<IpAddr>() {
+            Ok(ip) => Self::is_internal(ip),
+            Err(_) => host == "localhost" || host.ends_with(".localhost"),
+        };
+        if internal {
+            return Err("Url must not point to a loopback, private or link-local host".to_string());
+        }
+        if self
+            .events
+            .iter()
+            .any(|status| Self::event_name(*status).is_none())
+        {
+            return Err(
+                "Only COMPLETE, REJECTED, EXPIRED and REVERSED can be subscribed".to_string(),
+            );
+        }
+        Ok(self)
+    }
+
+    pub fn subscribes(&self, status: TxnStatus) -> bool {
+        self.events.is_empty() || self.events.contains(&status)
+    }
+
+    /// Sets a new encrypted secret and returns it readable, the only time it is
+    pub fn generate_secret(&mut self, keys: &KEY) -> Result<String, Status> {
+        let secret = to_hex(&Generator::generate_random_bytes());
+        self.secret = match encrypt(
+            keys.bytes,
+            secret.to_owned(),
+            &keys.secret,
+            &keys.seed,
+            keys.rate,
+        ) {
+            Ok(secret) => Some(secret),
+            Err(error) => {
+                println!("Error: {error}");
+                return Err(Status::InternalServerError);
+            }
+        };
+        Ok(secret)
+    }
+
+    pub fn secret(&self, keys: &KEY) -> Result<String, Status> {
+        let secret = match &self.secret {
+            Some(secret) => secret.to_owned(),
+            None => return Err(Status::NotFound),
+        };
+        // Decryption hands back the stored string as hex
+        match decrypt(keys.bytes, secret, &keys.secret, &keys.seed, keys.rate)
+            .map(from_hex)
+            .map(|bytes| bytes.map(String::from_utf8))
+        {
+            Ok(Ok(Ok(secret))) => Ok(secret),
+            _ => Err(Status::InternalServerError),
+        }
+    }
+}
+
+impl WebhookEvent {
+    pub fn new(event: &str, txn: &TRANSACTION, status: TxnStatus) -> Self {
+        Self {
+            event: event.to_string(),
+            id: txn.id.map(|id| id.to_hex()).unwrap_or_default(),
+            account: txn.account.clone(),
+            atm: txn.atm.clone(),
+            destination: txn.destination.clone(),
+            txn_type: txn.txn_type.to_string(),
+            status,
+            amount: txn.amount,
+            timestamp: timestamp_millis(),
+        }
+    }
+}
@@ -0,0 +1,45 @@
+use super::{handlers::Response, helpers::common::timestamp_millis};
+use mongodb::bson::{oid::ObjectId, DateTime};
+use serde::{Deserialize, Serialize};
+
+/// Value of the Idempotency-Key header, None when the header is missing
+pub struct IdempotencyKey(pub Option<String>);
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct IDEMPOTENCY {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub key: String,
+    pub sub: String, // Principal that sent the key
+    pub route: String,
+    #[serde(default)]
+    pub fingerprint: String, // Keyed hash of the request body, empty on keys stored before it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response: Option<Response<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>, // Set instead of response when the first request failed
+    #[serde(default)]
+    pub started: i64, // Millis the running request took the key, zero on keys stored before it
+    pub created: DateTime, // For expiry
+}
+
+impl IDEMPOTENCY {
+    pub const HEADER: &str = "Idempotency-Key";
+    pub const MAX_KEY_LENGTH: usize = 255;
+    pub const DEFAULT_EXPIRY: i64 = 86_400_000;
+    pub const ABANDONED_AFTER: i64 = 300_000; // A request still unfinished after this died with the server
+
+    pub fn new(key: &str, sub: &str, route: &str, fingerprint: String) -> Self {
+        Self {
+            id: None,
+            key: key.to_string(),
+            sub: sub.to_string(),
+            route: route.to_string(),
+            fingerprint,
+            response: None,
+            status: None,
+            started: timestamp_millis(),
+            created: DateTime::from_millis(timestamp_millis()),
+        }
+    }
+}
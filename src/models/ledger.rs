@@ -0,0 +1,36 @@
+use mongodb::bson::{oid::ObjectId, DateTime};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum Book {
+    ACCOUNT, // Customer balance, a liability of the bank
+    ATM,     // Cash held by a machine, an asset of the bank
+    PAYOUT,  // Balances of closed accounts paid out outside the ATMs, by method
+    OPENING, // Equity the balances held before the ledger was kept were opened against
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    DEBIT,
+    CREDIT,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ENTRY {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub txn: ObjectId, // Transaction or payout that moved the money, the account for an opening
+    pub book: Book,
+    pub name: String, // Account number or ATM name
+    pub side: Side,
+    pub amount: i64,
+    pub created: DateTime,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct DISCREPANCY {
+    pub number: String,
+    pub stored: i64,
+    pub ledger: i64,
+    pub difference: i64, // stored - ledger
+}
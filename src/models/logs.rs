@@ -1,4 +1,5 @@
-use mongodb::bson::oid::ObjectId;
+use super::fraud::ASSESSMENT;
+use mongodb::bson::{oid::ObjectId, Bson};
 use serde::{Deserialize, Serialize};
 use std::str::FromStr;
 
@@ -6,6 +7,11 @@ use std::str::FromStr;
 pub enum Nature {
     CREATION(CREATION),
     GENERATION(GENERATION),
+    EXPIRATION(EXPIRATION),
+    LOCKOUT(LOCKOUT),
+    REVERSAL(REVERSAL),
+    UPDATE(UPDATE),
+    SCREENING(SCREENING),
 }
 
 pub enum Type {
@@ -29,6 +35,46 @@ pub struct GENERATION {
     pub nature: String,
 }
 
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct EXPIRATION {
+    pub affected_id: ObjectId,
+    pub nature: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct LOCKOUT {
+    pub affected_id: ObjectId,
+    pub sub: String,
+    pub nature: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct REVERSAL {
+    pub affected_id: ObjectId,
+    pub reversal_id: ObjectId,
+    pub reason: String,
+    pub nature: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct UPDATE {
+    pub affected_id: ObjectId,
+    pub field: String,
+    pub old: Bson,
+    pub new: Bson,
+    pub nature: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct SCREENING {
+    pub affected_id: ObjectId, // Transaction, never stored when it was blocked
+    pub sub: String,           // Account number
+    pub score: i32,
+    pub decision: String,
+    pub rules: Vec<String>,
+    pub nature: String,
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct LOG {
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
@@ -82,6 +128,76 @@ impl LOG {
         self
     }
 
+    pub fn expiration(&mut self, affected_id: ObjectId) -> &mut Self {
+        self.change = Some(Nature::EXPIRATION(EXPIRATION {
+            affected_id,
+            nature: "EXPIRATION".to_string(),
+        }));
+        self
+    }
+
+    pub fn lockout(&mut self, affected_id: ObjectId, sub: String) -> &mut Self {
+        self.change = Some(Nature::LOCKOUT(LOCKOUT {
+            affected_id,
+            sub,
+            nature: "LOCKOUT".to_string(),
+        }));
+        self
+    }
+
+    pub fn reversal(
+        &mut self,
+        affected_id: ObjectId,
+        reversal_id: ObjectId,
+        reason: String,
+    ) -> &mut Self {
+        self.change = Some(Nature::REVERSAL(REVERSAL {
+            affected_id,
+            reversal_id,
+            reason,
+            nature: "REVERSAL".to_string(),
+        }));
+        self
+    }
+
+    pub fn update(
+        &mut self,
+        affected_id: ObjectId,
+        field: &str,
+        old: Bson,
+        new: Bson,
+    ) -> &mut Self {
+        self.change = Some(Nature::UPDATE(UPDATE {
+            affected_id,
+            field: field.to_string(),
+            old,
+            new,
+            nature: "UPDATE".to_string(),
+        }));
+        self
+    }
+
+    pub fn screening(
+        &mut self,
+        affected_id: ObjectId,
+        sub: String,
+        assessment: &ASSESSMENT,
+    ) -> &mut Self {
+        self.change = Some(Nature::SCREENING(SCREENING {
+            affected_id,
+            sub,
+            score: assessment.score,
+            decision: assessment.decision.to_string(),
+            rules: assessment
+                .signals
+                .iter()
+                .map(|signal| signal.rule.to_string())
+                .collect(),
+            nature: "SCREENING".to_string(),
+        }));
+        self
+    }
+
     pub fn build(&self) -> Self {
         Self {
             id: self.id,
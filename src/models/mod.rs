@@ -1,9 +1,17 @@
 pub mod admin;
 pub mod atm;
+pub mod events;
+pub mod fraud;
 pub mod handlers;
 pub mod helpers;
+pub mod idempotency;
 pub mod keys;
+pub mod ledger;
 pub mod logs;
+pub mod notification;
+pub mod permission;
+pub mod settings;
 pub mod token;
 pub mod transaction;
 pub mod user;
+pub mod webhook;
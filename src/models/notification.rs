@@ -0,0 +1,40 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Channel {
+    EMAIL,
+    SMS,
+    PUSH,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum Notice {
+    TxnCreated,   // Carries the one-time password
+    TxnChallenge, // Carries the verification code asked for by fraud screening
+    TxnCompleted, // Money moved
+    TxnRejected,  // Refused or locked
+    TxnReceived,  // Transfer paid into the destination account
+    TxnReversed,  // Transfer taken back from the destination account
+    NewDevice,    // Login from a device not seen before
+}
+
+/// Where an account holder wants to be told about activity, every field is optional
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct CONTACT {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub phone: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub push_token: Option<String>, // Device token issued by the push provider
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct NOTIFICATION {
+    pub channel: Channel,
+    pub recipient: String, // Address, number or device token for the channel
+    pub notice: Notice,
+    pub subject: String,
+    pub body: String,
+    pub timestamp: i64,
+}
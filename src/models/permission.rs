@@ -0,0 +1,84 @@
+use crate::models::admin::ADMIN;
+use mongodb::bson::oid::ObjectId;
+use std::{
+    collections::{HashMap, HashSet},
+    marker::PhantomData,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Permission {
+    AdminCreate,     // admin:create, only roles up to their own
+    AdminRead,       // admin:read
+    AdminManage,     // admin:manage, only admins of a lower role
+    AccountCreate,   // account:create
+    AccountRead,     // account:read
+    AccountPin,      // account:pin
+    AccountStatus,   // account:status
+    AccountClose,    // account:close
+    AtmCreate,       // atm:create
+    AtmRead,         // atm:read
+    AtmUpdate,       // atm:update, also activates and deactivates
+    AtmService,      // atm:service
+    AtmCassettes,    // atm:cassettes
+    AtmDelete,       // atm:delete
+    TxnRead,         // txn:read
+    TxnReverse,      // txn:reverse
+    LedgerReconcile, // ledger:reconcile
+    WebhookManage,   // webhook:manage
+}
+
+/// Permissions held by each admin role, keyed by the value of the role
+#[derive(Debug, Clone, Default)]
+pub struct PERMISSIONS {
+    pub roles: HashMap<i8, HashSet<Permission>>,
+}
+
+/// Ties a marker type to the permission a route needs
+pub trait Required {
+    const PERMISSION: Permission;
+}
+
+/// Marker types naming the permissions, used as `Permitted<can::AtmCreate>`
+pub mod can {
+    use super::{Permission, Required};
+
+    macro_rules! markers {
+        ($($name:ident),+) => {
+            // Only ever named as a type, some are checked by hand instead
+            $(#[allow(dead_code)]
+            pub struct $name;
+
+            impl Required for $name {
+                const PERMISSION: Permission = Permission::$name;
+            })+
+        };
+    }
+
+    markers!(
+        AdminCreate,
+        AdminRead,
+        AdminManage,
+        AccountCreate,
+        AccountRead,
+        AccountPin,
+        AccountStatus,
+        AccountClose,
+        AtmCreate,
+        AtmRead,
+        AtmUpdate,
+        AtmService,
+        AtmCassettes,
+        AtmDelete,
+        TxnRead,
+        TxnReverse,
+        LedgerReconcile,
+        WebhookManage
+    );
+}
+
+/// Request guard for an admin holding the permission of P, routes take it in place of TOKEN
+pub struct Permitted<P: Required> {
+    pub admin: ADMIN,
+    pub id: ObjectId, // Of the admin
+    pub permission: PhantomData<P>,
+}
@@ -0,0 +1,71 @@
+#[derive(Debug, Clone, Copy)]
+pub struct SETTINGS {
+    pub max_otp_attempts: i32, // Failed OTP or PIN entries before a transaction is locked
+    pub max_lockouts: i32,     // Locked transactions before the account is blocked
+    pub lockout_duration: i64, // Millis an account stays blocked
+    pub txn_limit: i64,        // Largest single withdrawal, 0 disables
+    pub daily_limit: i64,      // Withdrawals per account in a business day, 0 disables
+    pub atm_daily_limit: i64,  // Withdrawals per ATM in a business day, 0 disables
+    pub business_day_offset: i64, // Millis from UTC midnight to the start of a business day
+    pub totp_digits: u32,      // Length of a one-time password
+    pub totp_step: u64,        // Seconds a one-time password stays current
+    pub totp_skew: u64,        // Steps either side of the current one that are accepted
+    pub webhook_max_attempts: i32, // Deliveries tried before an event is given up
+    pub webhook_backoff: i64,  // Millis before the first retry, doubled after every failure
+    pub fraud_confirm_score: i32, // Fraud score from which the account holder has to confirm
+    pub fraud_block_score: i32, // Fraud score from which a transaction is refused
+    pub max_atm_distance: f64, // Meters a phone may be from the ATM it starts a withdrawal at, 0 disables
+    pub heartbeat_timeout: i64, // Millis without a heartbeat before an ATM is OFFLINE
+    pub low_cash_level: i64,   // Cash below which an ATM is DEGRADED
+    pub access_token_expiry: i64, // Millis an access token stays valid
+    pub refresh_token_expiry: i64, // Millis a login can be kept alive by refreshing
+}
+
+impl SETTINGS {
+    pub const DEFAULT_MAX_OTP_ATTEMPTS: i32 = 3;
+    pub const DEFAULT_MAX_LOCKOUTS: i32 = 3;
+    pub const DEFAULT_LOCKOUT_DURATION: i64 = 86_400_000;
+    pub const DEFAULT_TXN_LIMIT: i64 = 20_000;
+    pub const DEFAULT_DAILY_LIMIT: i64 = 50_000;
+    pub const DEFAULT_ATM_DAILY_LIMIT: i64 = 1_000_000;
+    pub const DAY: i64 = 86_400_000;
+    pub const DEFAULT_TOTP_DIGITS: u32 = 6;
+    pub const DEFAULT_TOTP_STEP: u64 = 30;
+    pub const DEFAULT_TOTP_SKEW: u64 = 1;
+    pub const DEFAULT_WEBHOOK_MAX_ATTEMPTS: i32 = 8;
+    pub const DEFAULT_WEBHOOK_BACKOFF: i64 = 10_000;
+    pub const MAX_WEBHOOK_BACKOFF: i64 = 3_600_000;
+    pub const DEFAULT_FRAUD_CONFIRM_SCORE: i32 = 50;
+    pub const DEFAULT_FRAUD_BLOCK_SCORE: i32 = 80;
+    pub const DEFAULT_MAX_ATM_DISTANCE: f64 = 500.0;
+    pub const DEFAULT_HEARTBEAT_TIMEOUT: i64 = 300_000;
+    pub const DEFAULT_LOW_CASH_LEVEL: i64 = 5_000;
+    pub const DEFAULT_ACCESS_TOKEN_EXPIRY: i64 = 900_000;
+    pub const DEFAULT_REFRESH_TOKEN_EXPIRY: i64 = 86_400_000;
+}
+
+impl Default for SETTINGS {
+    fn default() -> Self {
+        Self {
+            max_otp_attempts: Self::DEFAULT_MAX_OTP_ATTEMPTS,
+            max_lockouts: Self::DEFAULT_MAX_LOCKOUTS,
+            lockout_duration: Self::DEFAULT_LOCKOUT_DURATION,
+            txn_limit: Self::DEFAULT_TXN_LIMIT,
+            daily_limit: Self::DEFAULT_DAILY_LIMIT,
+            atm_daily_limit: Self::DEFAULT_ATM_DAILY_LIMIT,
+            business_day_offset: 0,
+            totp_digits: Self::DEFAULT_TOTP_DIGITS,
+            totp_step: Self::DEFAULT_TOTP_STEP,
+            totp_skew: Self::DEFAULT_TOTP_SKEW,
+            webhook_max_attempts: Self::DEFAULT_WEBHOOK_MAX_ATTEMPTS,
+            webhook_backoff: Self::DEFAULT_WEBHOOK_BACKOFF,
+            fraud_confirm_score: Self::DEFAULT_FRAUD_CONFIRM_SCORE,
+            fraud_block_score: Self::DEFAULT_FRAUD_BLOCK_SCORE,
+            max_atm_distance: Self::DEFAULT_MAX_ATM_DISTANCE,
+            heartbeat_timeout: Self::DEFAULT_HEARTBEAT_TIMEOUT,
+            low_cash_level: Self::DEFAULT_LOW_CASH_LEVEL,
+            access_token_expiry: Self::DEFAULT_ACCESS_TOKEN_EXPIRY,
+            refresh_token_expiry: Self::DEFAULT_REFRESH_TOKEN_EXPIRY,
+        }
+    }
+}
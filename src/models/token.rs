@@ -18,6 +18,24 @@ pub struct JWT {
     pub created: DateTime, // For expiry
 }
 
+/// Single use token traded for a new access token, only its hash is stored
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct REFRESH {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>, // key
+    pub hash: String,
+    pub family: ObjectId, // Shared by every token rotated from the same login
+    pub sub: ObjectId,
+    pub role: Type,
+    pub used: bool,
+    pub expires: DateTime, // End of the login, rotating does not extend it
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RefreshRequest {
+    pub refresh_token: String,
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct TOKEN {
     pub sub: String,
@@ -31,12 +49,13 @@ impl TOKEN {
     pub const INTERNAL_SERVER_ERROR: &str = "Internal Server Failure";
     pub const UNAUTHORIZED_ERROR: &str = "Unauthorized";
     pub const NOT_FOUND: &str = "Not Found";
+    pub const INACTIVE: &str = "Not Active";
     pub const BEARER: &str = "Bearer ";
-    pub const DEFAULT_EXPIRY: i64 = 86_400_000;
+    pub const DEFAULT_EXPIRY: i64 = 86_400_000; // Longest an access token may last, stored tokens expire after it
 
-    pub fn new(sub: String, role: String) -> Result<TOKEN, String> {
+    pub fn new(sub: String, role: String, expiry: i64) -> Result<TOKEN, String> {
         let timestamp = timestamp_millis();
-        let exp = Wrapping(timestamp + Self::DEFAULT_EXPIRY);
+        let exp = Wrapping(timestamp + expiry);
         let role = Type::from_str(&role)?;
 
         Ok(TOKEN {
@@ -62,3 +81,17 @@ impl JWT {
         self
     }
 }
+
+impl REFRESH {
+    pub fn new(hash: String, family: ObjectId, sub: ObjectId, role: Type, expires: i64) -> Self {
+        Self {
+            id: None,
+            hash,
+            family,
+            sub,
+            role,
+            used: false,
+            expires: DateTime::from_millis(expires),
+        }
+    }
+}
@@ -1,23 +1,39 @@
 use std::str::FromStr;
 
-use crate::utilities::crypto::Generator;
 use mongodb::bson::{oid::ObjectId, DateTime};
 use serde::{Deserialize, Serialize};
 
-use super::helpers::common::timestamp_millis;
+use super::{atm::Location, fraud::ASSESSMENT, helpers::common::timestamp_millis};
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
 pub enum TxnStatus {
     PENDING,  // Ongoing... wait for otp confirmation
     EXPIRED,  // Timeout
     COMPLETE, // Successful
     REJECTED, // Balance Insufficient
+    REVERSED, // Undone by a compensating transaction
 }
 
-#[derive(Debug, Deserialize, Serialize, PartialEq, Eq)]
+// Every status change a transaction may go through, anything else is refused
+pub const TXN_TRANSITIONS: [(TxnStatus, TxnStatus); 4] = [
+    (TxnStatus::PENDING, TxnStatus::COMPLETE),
+    (TxnStatus::PENDING, TxnStatus::REJECTED),
+    (TxnStatus::PENDING, TxnStatus::EXPIRED),
+    (TxnStatus::COMPLETE, TxnStatus::REVERSED),
+];
+
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq, Clone, Copy)]
 pub enum TxnType {
     DEBIT,
     CREDIT,
+    TRANSFER, // Account to account, account field holds the source
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TxnLimit {
+    TRANSACTION, // Single withdrawal
+    DAILY,       // Account total for the business day
+    ATM,         // ATM total for the business day
 }
 
 fn default_amount() -> i64 {
@@ -32,11 +48,15 @@ fn default_txn_type() -> TxnType {
     TxnType::DEBIT
 }
 
+fn default_attempts() -> i32 {
+    0
+}
+
 fn default_creation_time() -> DateTime {
     DateTime::from_millis(timestamp_millis())
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct TRANSACTION {
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
     pub id: Option<ObjectId>,
@@ -44,16 +64,74 @@ pub struct TRANSACTION {
     pub account: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub atm: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub destination: Option<String>,
     #[serde(default = "default_amount")]
     pub amount: i64,
     #[serde(default = "default_txn_type")]
     pub txn_type: TxnType,
     #[serde(default = "default_status")]
     pub status: TxnStatus,
+    #[serde(default = "default_attempts")]
+    pub attempts: i32, // Failed OTP or PIN entries
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub otp: Option<i16>,
+    pub reversal_of: Option<ObjectId>, // Set on a compensating transaction
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reversed_by: Option<ObjectId>, // Set on a reversed transaction
     #[serde(default = "default_creation_time")]
     pub created: DateTime,
+    #[serde(default)]
+    pub transitions: Vec<TRANSITION>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub coordinates: Option<Location>, // Reported by the phone that started the transaction
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub risk: Option<ASSESSMENT>, // Fraud screening done on creation
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub challenge: Option<String>, // Hashed code sent to the account holder when the risk asks for it
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct TRANSITION {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<TxnStatus>, // None when the transaction was created with the status
+    pub to: TxnStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub actor: Option<ObjectId>, // None for changes made by the server itself
+    pub role: String,
+    pub timestamp: i64,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Confirmation {
+    pub otp: String,
+    pub pin: String,
+    #[serde(default)]
+    pub challenge: Option<String>, // Only for transactions that need confirming
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Reversal {
+    pub reason: String,
+}
+
+pub use self::filter::TxnFilter;
+
+// Only around the filter, its FromForm derive emits the removed private_in_public lint
+#[allow(renamed_and_removed_lints)]
+mod filter {
+    #[derive(Debug, FromForm)]
+    pub struct TxnFilter {
+        pub account: Option<String>, // Admin only
+        pub atm: Option<String>,     // Admin only
+        pub status: Option<String>,
+        pub txn_type: Option<String>,
+        pub from: Option<i64>, // Millis
+        pub to: Option<i64>,   // Millis
+        pub min: Option<i64>,
+        pub max: Option<i64>,
+        pub cursor: Option<String>, // _id of the last transaction seen
+        pub limit: Option<i64>,
+    }
 }
 
 impl TRANSACTION {
@@ -65,16 +143,39 @@ impl TRANSACTION {
             id: None,
             account: Some(account.to_string()),
             atm: Some(atm.to_string()),
+            destination: None,
             amount,
             txn_type: TxnType::from_str(txn_type).unwrap(),
             status: TxnStatus::PENDING,
-            otp: Some(Generator::generate_number_i16(1000, 9999)),
+            attempts: default_attempts(),
+            reversal_of: None,
+            reversed_by: None,
             created,
+            transitions: Vec::new(),
+            coordinates: None,
+            risk: None,
+            challenge: None,
         }
     }
 
-    pub fn generate_otp(&mut self) -> &mut Self {
-        self.otp = Some(Generator::generate_number_i16(1000, 9999));
-        self
+    pub fn transfer(source: &str, destination: &str, amount: i64) -> Self {
+        let created = DateTime::from_millis(timestamp_millis());
+        Self {
+            id: None,
+            account: Some(source.to_string()),
+            atm: None,
+            destination: Some(destination.to_string()),
+            amount,
+            txn_type: TxnType::TRANSFER,
+            status: TxnStatus::PENDING,
+            attempts: default_attempts(),
+            reversal_of: None,
+            reversed_by: None,
+            created,
+            transitions: Vec::new(),
+            coordinates: None,
+            risk: None,
+            challenge: None,
+        }
     }
 }
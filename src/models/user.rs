@@ -1,10 +1,10 @@
 use crate::{
-    models::helpers::common::timestamp,
+    models::{helpers::common::timestamp, keys::KEY, notification::CONTACT},
     pwd,
-    utilities::crypto::{hasher, Generator},
+    utilities::crypto::{encrypt, hasher, to_hex, Generator},
 };
 use mongodb::bson::oid::ObjectId;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 
 fn default_balance() -> i64 {
     0
@@ -14,6 +14,63 @@ fn default_token() -> Option<ObjectId> {
     None
 }
 
+fn default_lockouts() -> i32 {
+    0
+}
+
+fn default_status() -> AccountStatus {
+    AccountStatus::ACTIVE
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum AccountStatus {
+    ACTIVE,  // Usable as usual
+    FROZEN,  // Compromised or under investigation, nothing moves in or out
+    DORMANT, // Unused for long, transfers still arrive but the holder has to be reactivated
+    CLOSED,  // Final, the balance is zero or was paid out
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum PayoutMethod {
+    CASH,
+    CHEQUE,
+    TRANSFER, // To an account outside of the bank
+}
+
+/// How the remaining balance of a closed account left the bank
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct PAYOUT {
+    pub method: PayoutMethod,
+    pub reference: String, // Receipt, cheque number or receiving account
+    #[serde(default)]
+    pub amount: i64, // Set to the balance paid out
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entry: Option<ObjectId>, // Ledger entries posted for the payout
+    #[serde(default)]
+    pub timestamp: i64, // Millis
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StatusChange {
+    pub status: AccountStatus,
+    pub payout: Option<PAYOUT>, // Needed to close an account that still holds money
+}
+
+// Accounts stored before hashing hold the PIN as a plain number
+fn deserialize_pin<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Pin {
+        Plain(i64),
+        Hashed(String),
+    }
+    Ok(match Option::<Pin>::deserialize(deserializer)? {
+        Some(Pin::Plain(pin)) => Some(pin.to_string()),
+        Some(Pin::Hashed(hash)) => Some(hash),
+        None => None,
+    })
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct ACCOUNT {
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
@@ -27,8 +84,32 @@ pub struct ACCOUNT {
     pub balance: i64,
     #[serde(skip_serializing_if = "Option::is_none", default = "default_token")]
     pub token: Option<ObjectId>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        default,
+        deserialize_with = "deserialize_pin"
+    )]
+    pub pin: Option<String>, // Hashed like the password
+    #[serde(default)]
+    pub pin_temporary: bool, // One-time PIN that has to be changed before transacting
+    #[serde(default = "default_lockouts")]
+    pub lockouts: i32, // Locked transactions since the last block
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blocked_until: Option<i64>, // Millis, no new transactions before this
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub totp_secret: Option<String>, // Encrypted, seeds the one-time passwords
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub pin: Option<i16>,
+    pub totp_counter: Option<i64>, // Step of the last accepted one-time password, it and earlier ones are refused
+    #[serde(default)]
+    pub totp_enrolled: bool, // Provisioning uri handed out, it is never sent again
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub contact: Option<CONTACT>, // Where activity is notified, nothing is sent without it
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub devices: Vec<String>, // Fingerprints of the devices that logged in before
+    #[serde(default = "default_status")]
+    pub status: AccountStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payout: Option<PAYOUT>, // Only on closed accounts that held money
 }
 
 impl ACCOUNT {
@@ -49,7 +130,17 @@ impl ACCOUNT {
             },
             balance: default_balance(),
             token: None,
-            pin: Some(Generator::generate_number_i16(1000, 9999)),
+            pin: None,
+            pin_temporary: false,
+            lockouts: default_lockouts(),
+            blocked_until: None,
+            totp_secret: None,
+            totp_counter: None,
+            totp_enrolled: false,
+            contact: None,
+            devices: Vec::new(),
+            status: default_status(),
+            payout: None,
         }
     }
 
@@ -60,11 +151,51 @@ impl ACCOUNT {
         self
     }
 
-    pub fn generate_pin(&mut self) -> &mut Self {
-        if self.pin.is_none() {
-            self.pin = Some(Generator::generate_number_i16(1000, 9999))
+    // Replaces the PIN with a one-time PIN and returns it unhashed, the only time it is readable
+    pub fn generate_pin(&mut self) -> Result<String, Status> {
+        let pin = Generator::generate_number_i16(1000, 9999).to_string();
+        self.hash_pin(&pin)?;
+        self.pin_temporary = true;
+        Ok(pin)
+    }
+
+    pub fn hash_pin(&mut self, pin: &str) -> Result<&mut Self, Status> {
+        self.pin = match hash_password_default(pin.to_string()) {
+            Ok(hash) => Some(hash),
+            Err(error) => {
+                println!("Error while hashing: {error}");
+                return Err(Status::InternalServerError);
+            }
+        };
+        self.pin_temporary = false;
+        Ok(self)
+    }
+
+    pub fn generate_totp_secret(&mut self, keys: &KEY) -> Result<&mut Self, Status> {
+        if self.totp_secret.is_none() {
+            let secret = to_hex(&Generator::generate_totp_secret());
+            self.totp_secret =
+                match encrypt(keys.bytes, secret, &keys.secret, &keys.seed, keys.rate) {
+                    Ok(secret) => Some(secret),
+                    Err(error) => {
+                        println!("Error: {error}");
+                        return Err(Status::InternalServerError);
+                    }
+                };
         }
-        self
+        Ok(self)
     }
 }
 pwd!(ACCOUNT);
+
+#[derive(Debug, Deserialize)]
+pub struct PinChange {
+    pub current: String,
+    pub new: String,
+}
+
+/// Fingerprint of the client sending a request, taken from its User-Agent
+pub struct Device {
+    pub fingerprint: String,
+    pub agent: String,
+}
@@ -0,0 +1,73 @@
+use super::{helpers::common::timestamp_millis, transaction::TxnStatus};
+use mongodb::bson::{oid::ObjectId, DateTime};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum DeliveryStatus {
+    PENDING,   // Waiting for the next attempt
+    DELIVERED, // Receiver answered with 2xx
+    FAILED,    // Out of attempts or the webhook was removed
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct WEBHOOK {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub url: String,
+    #[serde(default)]
+    pub events: Vec<TxnStatus>, // Empty subscribes to every event
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>, // Encrypted, signs the deliveries
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub creator: Option<ObjectId>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created: Option<DateTime>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct DELIVERY {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub webhook: ObjectId,
+    pub event: String,
+    pub payload: String, // Body exactly as signed and sent
+    pub status: DeliveryStatus,
+    pub attempts: i32,
+    pub next_attempt: i64, // Millis
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    pub created: DateTime,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct WebhookEvent {
+    pub event: String,
+    pub id: String, // Id of the transaction
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub account: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub atm: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub destination: Option<String>,
+    pub txn_type: String,
+    pub status: TxnStatus,
+    pub amount: i64,
+    pub timestamp: i64,
+}
+
+impl DELIVERY {
+    pub fn new(webhook: ObjectId, event: &str, payload: String) -> Self {
+        let now = timestamp_millis();
+        Self {
+            id: None,
+            webhook,
+            event: event.to_string(),
+            payload,
+            status: DeliveryStatus::PENDING,
+            attempts: 0,
+            next_attempt: now,
+            last_error: None,
+            created: DateTime::from_millis(now),
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use crate::{
+    check_if_400, check_if_401,
+    database::repository::Repository,
+    models::{
+        atm::{CassetteAdjustment, CASSETTE},
+        handlers::Response,
+        permission::{can, Permitted},
+        token::{Type, TOKEN},
+    },
+};
+use rocket::{http::Status, serde::json::Json, State};
+
+#[post("/atm/cassettes/load/<name>", data = "<cassettes>")]
+pub async fn load_cassettes(
+    permitted: Permitted<can::AtmCassettes>,
+    db: &State<Repository>,
+    cassettes: Json<Vec<CASSETTE>>,
+    name: String,
+) -> Result<Response<String>, Status> {
+    check_if_400!(CASSETTE::validate(&cassettes).is_err());
+
+    let admin_id = permitted.id;
+    db.load_cassettes(admin_id, &name, cassettes.0).await?;
+    Ok(Response::<String>::new()
+        .message(format!("Loaded Cassettes: {name}"))
+        .status(Status::Ok)
+        .clone())
+}
+
+#[post("/atm/cassettes/adjust/<name>", data = "<adjustment>")]
+pub async fn adjust_cassette(
+    permitted: Permitted<can::AtmCassettes>,
+    db: &State<Repository>,
+    adjustment: Json<CassetteAdjustment>,
+    name: String,
+) -> Result<Response<String>, Status> {
+    check_if_400!(adjustment.denomination <= 0 || adjustment.delta == 0);
+
+    let admin_id = permitted.id;
+    db.adjust_cassette(admin_id, &name, adjustment.0).await?;
+    Ok(Response::<String>::new()
+        .message(format!("Adjusted Cassette: {name}"))
+        .status(Status::Ok)
+        .clone())
+}
+
+#[post("/atm/dispense", data = "<notes>")]
+pub async fn report_dispense(
+    token: TOKEN,
+    db: &State<Repository>,
+    notes: Json<Vec<CASSETTE>>,
+) -> Result<Response<String>, Status> {
+    check_if_401!(!Type::ATM.cmp(&token.role.value()));
+    check_if_400!(CASSETTE::validate(&notes).is_err());
+
+    let atm = db.get_atm_from_id(&token.sub).await?;
+    db.record_dispense(&atm.name, &notes).await?;
+    Ok(Response::<String>::new()
+        .success()
+        .status(Status::Ok)
+        .clone())
+}
@@ -0,0 +1,31 @@
+use crate::{
+    check_if_400, check_if_401,
+    database::repository::Repository,
+    models::{
+        handlers::Response,
+        notification::CONTACT,
+        token::{Type, TOKEN},
+    },
+    option,
+};
+use rocket::{http::Status, serde::json::Json, State};
+
+// Replaces every contact detail, leaving all of them out turns notifications off
+#[post("/account/contact", data = "<contact>")]
+pub async fn update_contact(
+    token: TOKEN,
+    db: &State<Repository>,
+    contact: Json<CONTACT>,
+) -> Result<Response<CONTACT>, Status> {
+    check_if_401!(!Type::ACCOUNT.cmp(&token.role.value()));
+    check_if_400!(contact.validate().is_err());
+
+    let account = db.get_account_from_id(&token.sub).await?;
+    let id = option!(val -> account.id; {val} | {return Err(Status::InternalServerError)});
+    db.set_contact(id, "User", &account, &contact).await?;
+    Ok(Response::<CONTACT>::new()
+        .message("Contact Updated".to_string())
+        .data(contact.0)
+        .status(Status::Ok)
+        .clone())
+}
@@ -1,25 +1,23 @@
 use crate::{
-    check_if_400, check_if_401, check_ok_401,
+    check_if_400, check_if_401,
     database::repository::Repository,
     models::{
-        admin::{Role, ADMIN},
+        admin::ADMIN,
         atm::ATM,
         handlers::Response,
-        token::{Type, TOKEN},
-        user::ACCOUNT,
+        permission::{can, Permitted},
+        user::{AccountStatus, ACCOUNT},
     },
 };
 use rocket::{http::Status, serde::json::Json, State};
 
 #[post("/admin/create", data = "<new_admin>")]
 pub async fn create_admin(
-    token: TOKEN,
+    permitted: Permitted<can::AdminCreate>,
     db: &State<Repository>,
     new_admin: Json<ADMIN>,
 ) -> Result<Response<String>, Status> {
-    check_if_401!(!Type::ADMIN.cmp(&token.role.value()));
-
-    let admin = check_ok_401!(db.get_admin_from_id(&token.sub).await)?;
+    let admin = permitted.admin;
 
     let mut data = match new_admin.0.validate() {
         Ok(data) => data.clone(),
@@ -45,14 +43,11 @@ pub async fn create_admin(
 
 #[post("/atm/create", data = "<atm>")]
 pub async fn create_atm(
-    token: TOKEN,
+    permitted: Permitted<can::AtmCreate>,
     db: &State<Repository>,
     atm: Json<ATM>,
 ) -> Result<Response<String>, Status> {
-    check_if_401!(!Type::ADMIN.cmp(&token.role.value()));
-
-    let admin = check_ok_401!(db.get_admin_from_id(&token.sub).await)?;
-    check_if_401!(admin.role.unwrap() < Role::SUPERVISOR);
+    let admin = permitted.admin;
 
     let mut data = atm.0;
     data.hash_password()?;
@@ -70,21 +65,32 @@ pub async fn create_atm(
 
 #[post("/account/create", data = "<account>")]
 pub async fn create_account(
-    token: TOKEN,
+    permitted: Permitted<can::AccountCreate>,
     db: &State<Repository>,
     account: Json<ACCOUNT>,
 ) -> Result<Response<ACCOUNT>, Status> {
-    check_if_401!(!Type::ADMIN.cmp(&token.role.value()));
     check_if_400!(account.name.is_none());
 
-    let admin = check_ok_401!(db.get_admin_from_id(&token.sub).await)?;
+    let admin = permitted.admin;
 
     let mut data = match account.0.validate() {
         Ok(data) => data.clone(),
         Err(_) => return Err(Status::BadRequest),
     };
     data.generate_number();
-    data.generate_pin();
+    data.devices = Vec::new();
+    data.balance = 0; // Money only comes in through transactions, which the ledger records
+    data.status = AccountStatus::ACTIVE;
+    data.payout = None;
+    data.token = None;
+    data.lockouts = 0;
+    data.blocked_until = None;
+    // A secret from the request body would let whoever creates the account know its codes
+    data.totp_secret = None;
+    data.totp_counter = None;
+    data.totp_enrolled = false;
+    let pin = data.generate_pin()?;
+    data.generate_totp_secret(&db.keys)?;
     data.hash_password()?;
 
     let name = data.name.as_ref().unwrap().to_owned();
@@ -92,6 +98,8 @@ pub async fn create_account(
     match db.create_account(admin.id, data.clone()).await {
         Ok(_) => {
             data.password = "$SEALED$".to_string();
+            data.totp_secret = None;
+            data.pin = Some(pin); // One-time PIN, changed by the customer before transacting
             return Ok(Response::<ACCOUNT>::new()
                 .message(format!("Created Account: {}", name))
                 .data(data)
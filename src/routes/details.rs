@@ -2,8 +2,9 @@ use crate::{
     check_if_401,
     database::repository::Repository,
     models::{
-        atm::ATM,
+        atm::{NearbyFilter, ATM, NEARBY},
         handlers::Response,
+        permission::{can, Permitted},
         token::{Type, TOKEN},
         user::ACCOUNT,
     },
@@ -19,6 +20,8 @@ pub async fn get_account(
     check_if_401!(!Type::ACCOUNT.cmp(&token.role.value()));
     let mut account = db.get_account_from_id(&token.sub).await?;
     seal_details!(account);
+    account.totp_secret = None;
+    account.pin = None;
     Ok(Response::<ACCOUNT>::new()
         .data(account)
         .message("Account Details".to_string())
@@ -28,13 +31,14 @@ pub async fn get_account(
 
 #[get("/get/account/<number>")]
 pub async fn get_account_admin(
-    token: TOKEN,
+    _permitted: Permitted<can::AccountRead>,
     db: &State<Repository>,
     number: String,
 ) -> Result<Response<ACCOUNT>, Status> {
-    check_if_401!(!Type::ADMIN.cmp(&token.role.value()));
     let mut account = db.get_account(number).await?;
     seal_details!(account);
+    account.totp_secret = None;
+    account.pin = None;
     Ok(Response::<ACCOUNT>::new()
         .data(account)
         .message("Account Details".to_string())
@@ -47,6 +51,7 @@ pub async fn get_atm(token: TOKEN, db: &State<Repository>) -> Result<Response<AT
     check_if_401!(!Type::ATM.cmp(&token.role.value()));
     let mut atm = db.get_atm_from_id(&token.sub).await?;
     seal_details!(atm);
+    atm.state = atm.current_state(&db.settings);
     Ok(Response::<ATM>::new()
         .data(atm)
         .message("ATM Details".to_string())
@@ -56,16 +61,36 @@ pub async fn get_atm(token: TOKEN, db: &State<Repository>) -> Result<Response<AT
 
 #[get("/get/atm/<name>")]
 pub async fn get_atm_admin(
-    token: TOKEN,
+    _permitted: Permitted<can::AtmRead>,
     db: &State<Repository>,
     name: String,
 ) -> Result<Response<ATM>, Status> {
-    check_if_401!(!Type::ADMIN.cmp(&token.role.value()));
     let mut atm = db.get_atm(name).await?;
     seal_details!(atm);
+    atm.state = atm.current_state(&db.settings);
     Ok(Response::<ATM>::new()
         .data(atm)
         .message("ATM Details".to_string())
         .status(Status::Ok)
         .clone())
 }
+
+// Public so customers can find a machine before logging in
+#[get("/atm/nearest?<filter..>")]
+pub async fn get_nearest_atms(
+    db: &State<Repository>,
+    filter: NearbyFilter,
+) -> Result<Response<Vec<NEARBY>>, Status> {
+    let location = match filter.location() {
+        Ok(location) => location,
+        Err(_) => return Err(Status::BadRequest),
+    };
+    let atms = db
+        .get_nearest_atms(&location, filter.to_document(&db.settings), filter.limit())
+        .await?;
+    Ok(Response::<Vec<NEARBY>>::new()
+        .data(atms)
+        .message("Nearest ATMs".to_string())
+        .status(Status::Ok)
+        .clone())
+}
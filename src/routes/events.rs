@@ -0,0 +1,99 @@
+use crate::{
+    check_if_401,
+    database::repository::Repository,
+    models::{
+        events::TxnEvent,
+        token::{Type, TOKEN},
+    },
+    option,
+};
+use mongodb::bson::oid::ObjectId;
+use rocket::{
+    http::Status,
+    response::stream::{Event, EventStream},
+    tokio::{
+        select,
+        sync::broadcast::{error::RecvError, Receiver},
+        time::{interval, Duration},
+    },
+    Shutdown, State,
+};
+
+// Streams the events of one account or ATM until the client leaves, its token goes or the server stops
+fn txn_events(
+    db: Repository,
+    token: ObjectId,
+    mut events: Receiver<TxnEvent>,
+    current: Option<TxnEvent>,
+    field: &'static str,
+    value: String,
+    mut end: Shutdown,
+) -> EventStream![] {
+    EventStream! {
+        if let Some(event) = current {
+            yield Event::json(&event).event(TxnEvent::NAME);
+        }
+        let mut check = interval(Duration::from_secs(TxnEvent::SESSION_CHECK));
+        loop {
+            let event = select! {
+                event = events.recv() => match event {
+                    Ok(event) if event.concerns(field, &value) => Some(event),
+                    Ok(_) => continue,
+                    Err(RecvError::Closed) => break,
+                    Err(RecvError::Lagged(_)) => continue,
+                },
+                _ = check.tick() => None,
+                _ = &mut end => break,
+            };
+            // Checked before each event and every so often, logging out, revoking or freezing removes the token
+            if !db.token_alive(token).await {
+                break;
+            }
+            if let Some(event) = event {
+                yield Event::json(&event).event(TxnEvent::NAME);
+            }
+        }
+    }
+}
+
+#[get("/atm/txn/events")]
+pub async fn get_txn_events_atm(
+    token: TOKEN,
+    db: &State<Repository>,
+    end: Shutdown,
+) -> Result<EventStream![], Status> {
+    check_if_401!(!Type::ATM.cmp(&token.role.value()));
+    let atm = db.get_atm_from_id(&token.sub).await?;
+    let id = option!(val -> atm.token; {val} | {return Err(Status::Unauthorized)});
+
+    // Subscribe before reading so nothing written in between is missed
+    let events = db.subscribe_txn_events();
+    let current = db
+        .get_recent_txn("atm", &atm.name)
+        .await
+        .ok()
+        .map(|txn| TxnEvent::new(&txn, txn.status));
+    let db = db.inner().clone();
+    Ok(txn_events(db, id, events, current, "atm", atm.name, end))
+}
+
+#[get("/account/txn/events")]
+pub async fn get_txn_events_account(
+    token: TOKEN,
+    db: &State<Repository>,
+    end: Shutdown,
+) -> Result<EventStream![], Status> {
+    check_if_401!(!Type::ACCOUNT.cmp(&token.role.value()));
+    let account = db.get_account_from_id(&token.sub).await?;
+    let id = option!(val -> account.token; {val} | {return Err(Status::Unauthorized)});
+    let number = account.number.unwrap();
+
+    let events = db.subscribe_txn_events();
+    let current = db
+        .get_recent_txn("account", &number)
+        .await
+        .ok()
+        .map(|txn| TxnEvent::new(&txn, txn.status));
+    let db = db.inner().clone();
+    Ok(txn_events(db, id, events, current, "account", number, end))
+}
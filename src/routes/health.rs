@@ -0,0 +1,55 @@
+use crate::{
+    check_if_400, check_if_401,
+    database::repository::Repository,
+    models::{
+        atm::{ServiceChange, HEARTBEAT},
+        handlers::Response,
+        permission::{can, Permitted},
+        token::{Type, TOKEN},
+    },
+};
+use rocket::{http::Status, serde::json::Json, State};
+
+#[post("/atm/heartbeat", data = "<heartbeat>")]
+pub async fn report_heartbeat(
+    token: TOKEN,
+    db: &State<Repository>,
+    heartbeat: Json<HEARTBEAT>,
+) -> Result<Response<String>, Status> {
+    check_if_401!(!Type::ATM.cmp(&token.role.value()));
+    check_if_400!(heartbeat.validate().is_err());
+
+    let atm = db.get_atm_from_id(&token.sub).await?;
+    let state = db.record_heartbeat(&atm, heartbeat.0).await?;
+    Ok(Response::<String>::new()
+        .message("Heartbeat Recorded".to_string())
+        .data(state.to_string())
+        .status(Status::Ok)
+        .clone())
+}
+
+#[post("/admin/atm/service/<name>", data = "<change>")]
+pub async fn set_atm_service(
+    permitted: Permitted<can::AtmService>,
+    db: &State<Repository>,
+    change: Json<ServiceChange>,
+    name: String,
+) -> Result<Response<String>, Status> {
+    let atm = db.get_atm(name).await?;
+    if !atm.active {
+        return Ok(Response::<String>::new()
+            .fail()
+            .error("ATM Deactivated".to_string())
+            .status(Status::NotAcceptable)
+            .clone());
+    }
+    let admin_id = permitted.id;
+    let state = db
+        .set_out_of_service(admin_id, &atm, change.out_of_service)
+        .await?;
+    Ok(Response::<String>::new()
+        .message(format!("ATM State: {}", atm.name))
+        .data(state.to_string())
+        .status(Status::Ok)
+        .clone())
+}
@@ -0,0 +1,92 @@
+use crate::{
+    check_if_400, check_if_401,
+    database::repository::Repository,
+    models::{
+        handlers::{Paging, Response},
+        permission::{can, Permitted},
+        token::{Type, TOKEN},
+        transaction::{TxnFilter, TRANSACTION},
+    },
+};
+use rocket::{http::Status, State};
+
+async fn txn_history(
+    db: &Repository,
+    filter: TxnFilter,
+) -> Result<Response<Vec<TRANSACTION>>, Status> {
+    let limit = filter.limit();
+    let query = match filter.to_document() {
+        Ok(query) => query,
+        Err(_) => return Err(Status::BadRequest),
+    };
+
+    let (mut txns, next) = db.get_txn_history(query, limit).await?;
+    txns.iter_mut().for_each(|txn| txn.challenge = None);
+
+    let paging = Paging {
+        limit,
+        count: txns.len(),
+        next: next.map(|id| id.to_hex()),
+    };
+    Ok(Response::<Vec<TRANSACTION>>::new()
+        .success()
+        .data(txns)
+        .paging(paging)
+        .status(Status::Ok)
+        .clone())
+}
+
+#[get("/account/txn/history?<filter..>")]
+pub async fn get_txn_history_account(
+    token: TOKEN,
+    db: &State<Repository>,
+    filter: TxnFilter,
+) -> Result<Response<Vec<TRANSACTION>>, Status> {
+    check_if_401!(!Type::ACCOUNT.cmp(&token.role.value()));
+    let account = db.get_account_from_id(&token.sub).await?;
+
+    let mut filter = filter;
+    filter.account = account.number;
+    filter.atm = None;
+    txn_history(db, filter).await
+}
+
+#[get("/atm/txn/history?<filter..>")]
+pub async fn get_txn_history_atm(
+    token: TOKEN,
+    db: &State<Repository>,
+    filter: TxnFilter,
+) -> Result<Response<Vec<TRANSACTION>>, Status> {
+    check_if_401!(!Type::ATM.cmp(&token.role.value()));
+    let atm = db.get_atm_from_id(&token.sub).await?;
+
+    let mut filter = filter;
+    filter.account = None;
+    filter.atm = Some(atm.name);
+    txn_history(db, filter).await
+}
+
+#[get("/admin/txn/history?<filter..>")]
+pub async fn get_txn_history_admin(
+    _permitted: Permitted<can::TxnRead>,
+    db: &State<Repository>,
+    filter: TxnFilter,
+) -> Result<Response<Vec<TRANSACTION>>, Status> {
+    check_if_400!(filter.account.is_none() && filter.atm.is_none());
+    txn_history(db, filter).await
+}
+
+#[get("/admin/txn/<id>")]
+pub async fn get_txn_admin(
+    _permitted: Permitted<can::TxnRead>,
+    db: &State<Repository>,
+    id: String,
+) -> Result<Response<TRANSACTION>, Status> {
+    let mut txn = db.get_txn_from_id(&id).await?;
+    txn.challenge = None;
+    Ok(Response::<TRANSACTION>::new()
+        .success()
+        .data(txn)
+        .status(Status::Ok)
+        .clone())
+}
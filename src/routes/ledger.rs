@@ -0,0 +1,26 @@
+use crate::{
+    database::repository::Repository,
+    models::{
+        handlers::Response,
+        ledger::DISCREPANCY,
+        permission::{can, Permitted},
+    },
+};
+use rocket::{http::Status, State};
+
+#[get("/admin/ledger/reconcile")]
+pub async fn reconcile_ledger(
+    _permitted: Permitted<can::LedgerReconcile>,
+    db: &State<Repository>,
+) -> Result<Response<Vec<DISCREPANCY>>, Status> {
+    let discrepancies = db.reconcile_ledger().await?;
+    let message = match discrepancies.is_empty() {
+        true => "Ledger Balanced".to_string(),
+        false => format!("{} Accounts Differ", discrepancies.len()),
+    };
+    Ok(Response::<Vec<DISCREPANCY>>::new()
+        .message(message)
+        .data(discrepancies)
+        .status(Status::Ok)
+        .clone())
+}
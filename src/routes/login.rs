@@ -1,10 +1,15 @@
 use crate::{
-    check_if_400, check_ok_404,
+    check_if_400, check_if_403, check_ok_404,
     database::repository::Repository,
     models::{
-        admin::ADMIN, atm::ATM, handlers::Response, helpers::common::timestamp_millis,
-        user::ACCOUNT,
+        admin::ADMIN,
+        atm::ATM,
+        handlers::Response,
+        helpers::common::timestamp_millis,
+        token::{RefreshRequest, Type, TOKEN},
+        user::{Device, ACCOUNT},
     },
+    option,
     utilities::crypto::verify_password,
 };
 use rocket::{http::Status, serde::json::Json, State};
@@ -21,6 +26,7 @@ pub async fn login_admin(
     let time = timestamp_millis();
     let data = admin.0;
     let admin = db.get_admin(data.username.to_owned()).await?;
+    check_if_403!(!admin.active);
     println!("Found Admin in time {}", timestamp_millis() - time);
 
     let authentication = check_ok_404!(verify_password(&data.password, &admin.password))?;
@@ -31,12 +37,15 @@ pub async fn login_admin(
     );
     match authentication {
         true => {
-            let token = db.login_admin(admin.id.unwrap(), admin.username).await?;
+            let sub = admin.id.unwrap();
+            let token = db.login_admin(sub, admin.username).await?;
+            let refresh = db.issue_refresh_token(sub, Type::ADMIN, None).await?;
             println!("Got Token in time {}", timestamp_millis() - time);
             Ok(Response::<String>::new()
                 .message("Login Successful".to_string())
                 .status(Status::Ok)
                 .token(token)
+                .refresh(refresh)
                 .clone())
         }
         false => Err(Status::NotFound),
@@ -47,15 +56,19 @@ pub async fn login_admin(
 pub async fn login_atm(db: &State<Repository>, atm: Json<ATM>) -> Result<Response<String>, Status> {
     let data = atm.0;
     let atm = db.get_atm(data.name.to_owned()).await?;
+    check_if_403!(!atm.active);
 
     let authentication = check_ok_404!(verify_password(&data.password, &atm.password))?;
     match authentication {
         true => {
-            let token = db.login_atm(atm.id.unwrap(), atm.name).await?;
+            let sub = atm.id.unwrap();
+            let token = db.login_atm(sub, atm.name).await?;
+            let refresh = db.issue_refresh_token(sub, Type::ATM, None).await?;
             Ok(Response::<String>::new()
                 .message("Login Successful".to_string())
                 .status(Status::Ok)
                 .token(token)
+                .refresh(refresh)
                 .clone())
         }
         false => Err(Status::NotFound),
@@ -65,6 +78,7 @@ pub async fn login_atm(db: &State<Repository>, atm: Json<ATM>) -> Result<Respons
 #[post("/account/login", data = "<account>")]
 pub async fn login_account(
     db: &State<Repository>,
+    device: Device,
     account: Json<ACCOUNT>,
 ) -> Result<Response<String>, Status> {
     let data = account.0;
@@ -72,19 +86,73 @@ pub async fn login_account(
 
     let number = data.number.as_ref().unwrap().to_owned();
     let account = db.get_account(number).await?;
+    check_if_403!(!account.is_active());
 
     let authentication = check_ok_404!(verify_password(&data.password, &account.password))?;
     match authentication {
         true => {
+            let sub = account.id.unwrap();
             let token = db
-                .login_account(account.id.unwrap(), account.number.unwrap())
+                .login_account(sub, account.number.clone().unwrap())
                 .await?;
+            let refresh = db.issue_refresh_token(sub, Type::ACCOUNT, None).await?;
+            if let Err(error) = db.record_device(&account, &device).await {
+                println!("Recording device failed: {error}");
+            }
             Ok(Response::<String>::new()
                 .message("Login Successful".to_string())
                 .status(Status::Ok)
                 .token(token)
+                .refresh(refresh)
                 .clone())
         }
         false => Err(Status::NotFound),
     }
 }
+
+// Trades a refresh token for a new token and refresh token, reusing one ends the session
+#[post("/token/refresh", data = "<request>")]
+pub async fn refresh_token(
+    db: &State<Repository>,
+    request: Json<RefreshRequest>,
+) -> Result<Response<String>, Status> {
+    let refresh = db.rotate_refresh_token(&request.refresh_token).await?;
+    let sub = refresh.sub;
+    let token = match refresh.role {
+        Type::ADMIN => {
+            let admin = db.get_admin_from_id(&sub.to_string()).await?;
+            check_if_403!(!admin.active);
+            db.login_admin(sub, admin.username).await?
+        }
+        Type::ATM => {
+            let atm = db.get_atm_from_id(&sub.to_string()).await?;
+            check_if_403!(!atm.active);
+            db.login_atm(sub, atm.name).await?
+        }
+        Type::ACCOUNT => {
+            let account = db.get_account_from_id(&sub.to_string()).await?;
+            check_if_403!(!account.is_active());
+            let number = option!(val -> account.number; {val} | {return Err(Status::NotFound)});
+            db.login_account(sub, number).await?
+        }
+    };
+    let next = db
+        .issue_refresh_token(sub, refresh.role, Some(&refresh))
+        .await?;
+    Ok(Response::<String>::new()
+        .message("Token Refreshed".to_string())
+        .status(Status::Ok)
+        .token(token)
+        .refresh(next)
+        .clone())
+}
+
+// Ends the session of whoever holds the token along with its refresh tokens
+#[post("/logout")]
+pub async fn logout(token: TOKEN, db: &State<Repository>) -> Result<Response<String>, Status> {
+    db.revoke_session(&token.sub, token.role).await?;
+    Ok(Response::<String>::new()
+        .message("Logout Successful".to_string())
+        .status(Status::Ok)
+        .clone())
+}
@@ -91,6 +91,13 @@ macro_rules! check_if_400 {
     };
 }
 
+#[macro_export]
+macro_rules! check_if_403 {
+    ($expression:expr) => {
+        crate::status_if!($expression, rocket::http::Status::Forbidden)
+    };
+}
+
 #[macro_export]
 macro_rules! check_if_406 {
     ($expression:expr) => {
@@ -105,3 +112,35 @@ macro_rules! seal_details {
         $object.password = "$SEALED$".to_string();
     };
 }
+
+#[macro_export]
+macro_rules! idempotent {
+    ($db:expr, $key:expr, $sub:expr, $route:literal, $request:expr, $body:block) => {{
+        match $key.0 {
+            Some(key) => match $db
+                .begin_idempotent(
+                    &key,
+                    &$sub,
+                    $route,
+                    crate::models::idempotency::IDEMPOTENCY::fingerprint(
+                        &$request,
+                        &$db.keys.secret,
+                    ),
+                )
+                .await?
+            {
+                Some(stored) => stored,
+                None => {
+                    let result: Result<crate::models::handlers::Response<String>, Status> =
+                        async $body.await;
+                    crate::check_result!(
+                        $db.finish_idempotent(&key, &$sub, &result).await,
+                        "Storing Idempotent Result"
+                    );
+                    result
+                }
+            },
+            None => async $body.await,
+        }
+    }};
+}
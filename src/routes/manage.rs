@@ -0,0 +1,237 @@
+use crate::{
+    check_if_400, check_if_401, check_ok_500,
+    database::repository::Repository,
+    models::{
+        admin::{PasswordReset, RoleChange, ADMIN},
+        atm::AtmUpdate,
+        handlers::Response,
+        permission::{can, Permission, Permitted},
+        user::{AccountStatus, StatusChange},
+    },
+    seal_details,
+    utilities::crypto::hash_password_default,
+};
+use mongodb::bson::Bson;
+use rocket::{http::Status, serde::json::Json, State};
+
+#[post("/admin/atm/update/<name>", data = "<changes>")]
+pub async fn update_atm(
+    permitted: Permitted<can::AtmUpdate>,
+    db: &State<Repository>,
+    changes: Json<AtmUpdate>,
+    name: String,
+) -> Result<Response<String>, Status> {
+    check_if_400!(changes.validate().is_err());
+
+    let atm = db.get_atm(name).await?;
+    let admin_id = permitted.id;
+    db.update_atm(admin_id, &atm, changes.0).await?;
+    Ok(Response::<String>::new()
+        .message(format!("Updated ATM: {}", atm.name))
+        .status(Status::Ok)
+        .clone())
+}
+
+#[post("/admin/atm/deactivate/<name>")]
+pub async fn deactivate_atm(
+    permitted: Permitted<can::AtmUpdate>,
+    db: &State<Repository>,
+    name: String,
+) -> Result<Response<String>, Status> {
+    let atm = db.get_atm(name).await?;
+    let admin_id = permitted.id;
+    let state = db.set_atm_active(admin_id, &atm, false).await?;
+    Ok(Response::<String>::new()
+        .message(format!("Deactivated ATM: {}", atm.name))
+        .data(state.to_string())
+        .status(Status::Ok)
+        .clone())
+}
+
+#[post("/admin/atm/activate/<name>")]
+pub async fn activate_atm(
+    permitted: Permitted<can::AtmUpdate>,
+    db: &State<Repository>,
+    name: String,
+) -> Result<Response<String>, Status> {
+    let atm = db.get_atm(name).await?;
+    let admin_id = permitted.id;
+    let state = db.set_atm_active(admin_id, &atm, true).await?;
+    Ok(Response::<String>::new()
+        .message(format!("Activated ATM: {}", atm.name))
+        .data(state.to_string())
+        .status(Status::Ok)
+        .clone())
+}
+
+#[delete("/admin/atm/<name>")]
+pub async fn delete_atm(
+    permitted: Permitted<can::AtmDelete>,
+    db: &State<Repository>,
+    name: String,
+) -> Result<Response<String>, Status> {
+    let atm = db.get_atm(name).await?;
+    // Only deactivated ATMs can go, so nothing is still running against them
+    if atm.active {
+        return Ok(Response::<String>::new()
+            .fail()
+            .error("ATM Still Active".to_string())
+            .status(Status::NotAcceptable)
+            .clone());
+    }
+    let admin_id = permitted.id;
+    db.delete_atm(admin_id, &atm).await?;
+    Ok(Response::<String>::new()
+        .message(format!("Deleted ATM: {}", atm.name))
+        .status(Status::Ok)
+        .clone())
+}
+
+#[post("/admin/account/status/<number>", data = "<change>")]
+pub async fn set_account_status(
+    permitted: Permitted<can::AccountStatus>,
+    db: &State<Repository>,
+    change: Json<StatusChange>,
+    number: String,
+) -> Result<Response<String>, Status> {
+    let admin = permitted.admin;
+    // Closing is final and needs its own permission on top
+    let closing = change.status == AccountStatus::CLOSED;
+    check_if_401!(closing && !db.permissions.allows(admin.role, Permission::AccountClose));
+    if let Some(payout) = &change.payout {
+        check_if_400!(payout.validate().is_err());
+    }
+
+    let account = db.get_account(number).await?;
+    if !account.status.can_change_to(change.status) {
+        return Ok(Response::<String>::new()
+            .fail()
+            .error(format!("Account Already {}", account.status.to_string()))
+            .status(Status::NotAcceptable)
+            .clone());
+    }
+    if change.status == AccountStatus::CLOSED && account.balance != 0 && change.payout.is_none() {
+        return Ok(Response::<String>::new()
+            .fail()
+            .error("Balance Not Paid Out".to_string())
+            .status(Status::NotAcceptable)
+            .clone());
+    }
+    let admin_id = permitted.id;
+    let StatusChange { status, payout } = change.0;
+    db.set_account_status(admin_id, &account, status, payout)
+        .await?;
+    Ok(Response::<String>::new()
+        .message(format!("Account Status: {}", status.to_string()))
+        .status(Status::Ok)
+        .clone())
+}
+
+#[get("/admin/list")]
+pub async fn get_admins(
+    _permitted: Permitted<can::AdminRead>,
+    db: &State<Repository>,
+) -> Result<Response<Vec<ADMIN>>, Status> {
+    let mut admins = db.get_admins().await?;
+    admins.iter_mut().for_each(|admin| {
+        seal_details!(admin);
+        admin.token = None;
+    });
+    Ok(Response::<Vec<ADMIN>>::new()
+        .success()
+        .data(admins)
+        .status(Status::Ok)
+        .clone())
+}
+
+#[post("/admin/role/<username>", data = "<change>")]
+pub async fn change_admin_role(
+    permitted: Permitted<can::AdminManage>,
+    db: &State<Repository>,
+    change: Json<RoleChange>,
+    username: String,
+) -> Result<Response<String>, Status> {
+    let admin = permitted.admin;
+    let target = db.get_admin(username).await?;
+    check_if_401!(!admin.outranks(&target));
+    // Nobody hands out a role as high as their own
+    check_if_401!(admin.role.map_or(true, |role| role <= change.role));
+
+    let admin_id = permitted.id;
+    let old = target
+        .role
+        .map(|role| Bson::String(role.to_string()))
+        .unwrap_or(Bson::Null);
+    let new = Bson::String(change.role.to_string());
+    db.update_admin(admin_id, &target, "role", new.clone(), (old, new))
+        .await?;
+    Ok(Response::<String>::new()
+        .message(format!("Admin Role: {}", change.role.to_string()))
+        .status(Status::Ok)
+        .clone())
+}
+
+#[post("/admin/disable/<username>")]
+pub async fn disable_admin(
+    permitted: Permitted<can::AdminManage>,
+    db: &State<Repository>,
+    username: String,
+) -> Result<Response<String>, Status> {
+    let admin = permitted.admin;
+    let target = db.get_admin(username).await?;
+    check_if_401!(!admin.outranks(&target));
+
+    let admin_id = permitted.id;
+    let (old, new) = (Bson::Boolean(target.active), Bson::Boolean(false));
+    db.update_admin(admin_id, &target, "active", new.clone(), (old, new))
+        .await?;
+    Ok(Response::<String>::new()
+        .message(format!("Disabled Admin: {}", target.username))
+        .status(Status::Ok)
+        .clone())
+}
+
+#[post("/admin/enable/<username>")]
+pub async fn enable_admin(
+    permitted: Permitted<can::AdminManage>,
+    db: &State<Repository>,
+    username: String,
+) -> Result<Response<String>, Status> {
+    let admin = permitted.admin;
+    let target = db.get_admin(username).await?;
+    check_if_401!(!admin.outranks(&target));
+
+    let admin_id = permitted.id;
+    let (old, new) = (Bson::Boolean(target.active), Bson::Boolean(true));
+    db.update_admin(admin_id, &target, "active", new.clone(), (old, new))
+        .await?;
+    Ok(Response::<String>::new()
+        .message(format!("Enabled Admin: {}", target.username))
+        .status(Status::Ok)
+        .clone())
+}
+
+#[post("/admin/password/reset/<username>", data = "<reset>")]
+pub async fn reset_admin_password(
+    permitted: Permitted<can::AdminManage>,
+    db: &State<Repository>,
+    reset: Json<PasswordReset>,
+    username: String,
+) -> Result<Response<String>, Status> {
+    check_if_400!(reset.validate().is_err());
+
+    let admin = permitted.admin;
+    let target = db.get_admin(username).await?;
+    check_if_401!(!admin.outranks(&target));
+
+    let admin_id = permitted.id;
+    let hash = check_ok_500!(hash_password_default(reset.0.password), "Hashing")?;
+    let sealed = Bson::String("$SEALED$".to_string());
+    let logged = (sealed.clone(), sealed);
+    db.update_admin(admin_id, &target, "password", Bson::String(hash), logged)
+        .await?;
+    Ok(Response::<String>::new()
+        .message(format!("Password Reset: {}", target.username))
+        .status(Status::Ok)
+        .clone())
+}
@@ -1,6 +1,15 @@
+pub mod cassette;
 pub mod create;
 pub mod login;
 #[macro_use]
 pub mod macros;
+pub mod contact;
 pub mod details;
+pub mod events;
+pub mod health;
+pub mod history;
+pub mod ledger;
+pub mod manage;
+pub mod pin;
 pub mod transaction;
+pub mod webhook;
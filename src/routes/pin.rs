@@ -0,0 +1,60 @@
+use crate::{
+    check_if_400, check_if_401,
+    database::repository::Repository,
+    models::{
+        handlers::Response,
+        permission::{can, Permitted},
+        token::{Type, TOKEN},
+        user::{PinChange, ACCOUNT},
+    },
+    option,
+};
+use rocket::{http::Status, serde::json::Json, State};
+
+#[post("/account/pin/change", data = "<change>")]
+pub async fn change_pin(
+    token: TOKEN,
+    db: &State<Repository>,
+    change: Json<PinChange>,
+) -> Result<Response<String>, Status> {
+    check_if_401!(!Type::ACCOUNT.cmp(&token.role.value()));
+    check_if_400!(ACCOUNT::validate_pin(&change.new).is_err());
+
+    let account = db.get_account_from_id(&token.sub).await?;
+    check_if_403!(account.is_blocked());
+    let number = account.number.as_ref().unwrap();
+
+    // Wrong PINs count towards a block so the PIN cannot be guessed here instead
+    if !db.verify_pin(&account, &change.current).await? {
+        db.record_lockout(number).await?;
+        return Err(Status::NotAcceptable);
+    }
+    check_if_406!(change.current == change.new);
+
+    let mut data = account.clone();
+    data.hash_pin(&change.new)?;
+    let id = option!(val -> account.id; {val} | {return Err(Status::InternalServerError)});
+    db.set_pin(id, "User", &data).await?;
+    Ok(Response::<String>::new()
+        .message("PIN Changed".to_string())
+        .status(Status::Ok)
+        .clone())
+}
+
+#[post("/admin/account/pin/reset/<number>")]
+pub async fn reset_pin_admin(
+    permitted: Permitted<can::AccountPin>,
+    db: &State<Repository>,
+    number: String,
+) -> Result<Response<String>, Status> {
+    let mut account = db.get_account(number).await?;
+    let pin = account.generate_pin()?;
+
+    let admin_id = permitted.id;
+    db.set_pin(admin_id, "Admin", &account).await?;
+    Ok(Response::<String>::new()
+        .message("PIN Reset".to_string())
+        .data(pin)
+        .status(Status::Ok)
+        .clone())
+}
@@ -1,11 +1,16 @@
 use crate::{
-    check_if_401, check_result,
+    check_if_400, check_if_401, check_result,
     database::repository::Repository,
     models::{
+        atm::AtmState,
+        fraud::Decision,
         handlers::Response,
+        idempotency::IdempotencyKey,
+        permission::{can, Permitted},
         token::{Type, TOKEN},
-        transaction::{TxnType, TRANSACTION},
+        transaction::{Confirmation, Reversal, TxnStatus, TxnType, TRANSACTION},
     },
+    option,
 };
 use rocket::{http::Status, serde::json::Json, State};
 
@@ -14,38 +19,158 @@ pub async fn create_txn_account(
     token: TOKEN,
     db: &State<Repository>,
     txn: Json<TRANSACTION>,
+    key: IdempotencyKey,
 ) -> Result<Response<String>, Status> {
     check_if_401!(!Type::ACCOUNT.cmp(&token.role.value()));
-    check_if_406!(txn.atm.is_none());
-    let account = db.get_account_from_id(&token.sub).await?;
-    let number = account.number.as_ref().unwrap();
-    let atm = db.get_atm(txn.atm.as_ref().unwrap().to_owned()).await?;
-    let amount = txn.amount;
-    let mut txn = TRANSACTION::new(number, &atm.name, "DEBIT", amount);
-    check_result!(
-        db.reject_all_pending_txn("account", &number).await,
-        "Reject All Pending Txn"
-    );
-    match txn.is_valid(account.balance) {
-        true => {
-            let otp = txn.generate_otp().otp.unwrap();
+    idempotent!(db, key, token.sub, "/account/txn/create", *txn, {
+        check_if_406!(txn.atm.is_none());
+        check_if_406!(txn.amount <= 0);
+        let account = db.get_account_from_id(&token.sub).await?;
+        check_if_403!(!account.can_transact());
+        let number = account.number.as_ref().unwrap();
+        let atm = db.get_atm(txn.atm.as_ref().unwrap().to_owned()).await?;
+        if atm.current_state(&db.settings) != AtmState::ONLINE {
+            return Ok(Response::<String>::new()
+                .fail()
+                .error("ATM Not In Service".to_string())
+                .status(Status::NotAcceptable)
+                .clone());
+        }
+        if let Some(coordinates) = &txn.coordinates {
+            if !atm.is_within(coordinates, db.settings.max_atm_distance) {
+                return Ok(Response::<String>::new()
+                    .fail()
+                    .error("Too Far From ATM".to_string())
+                    .status(Status::NotAcceptable)
+                    .clone());
+            }
+        }
+        let amount = txn.amount;
+        if !atm.can_dispense(amount) {
+            return Ok(Response::<String>::new()
+                .fail()
+                .error("Amount Cannot Be Dispensed".to_string())
+                .status(Status::NotAcceptable)
+                .clone());
+        }
+        if let Some(limit) = db.check_txn_limits(number, &atm.name, amount).await? {
+            return Ok(Response::<String>::new()
+                .fail()
+                .error(limit.value())
+                .status(Status::NotAcceptable)
+                .clone());
+        }
+        let coordinates = txn.coordinates;
+        let mut txn = TRANSACTION::new(number, &atm.name, "DEBIT", amount);
+        txn.coordinates = coordinates;
+        let assessment = db
+            .screen_txn(account.id, "User", &account, &atm, &mut txn)
+            .await?;
+        if assessment.decision == Decision::BLOCK {
+            return Ok(Response::<String>::new()
+                .fail()
+                .error("Transaction Blocked".to_string())
+                .status(Status::Forbidden)
+                .clone());
+        }
+        let challenge = match assessment.decision {
+            Decision::CONFIRM => Some(txn.generate_challenge()?),
+            _ => None,
+        };
+        check_result!(
+            db.reject_all_pending_txn("account", &number, account.id, "User")
+                .await,
+            "Reject All Pending Txn"
+        );
+        match txn.is_valid(account.balance) {
+            true => {
+                check_ok_500!(
+                    db.create_txn(account.id, "User", txn.clone()).await,
+                    "Creating Transaction"
+                )?;
+                if let Some(challenge) = challenge {
+                    db.send_challenge(&account, &txn, challenge);
+                    return Ok(Response::<String>::new()
+                        .message("Transaction Created, Verification Required".to_string())
+                        .status(Status::Ok)
+                        .clone());
+                }
+                Ok(Response::<String>::new()
+                    .message("Transaction Created".to_string())
+                    .status(Status::Ok)
+                    .clone())
+            }
+            false => {
+                check_ok_500!(
+                    db.create_txn(account.id, "User", txn).await,
+                    "Creating Transaction"
+                )?;
+                Ok(Response::<String>::new()
+                    .fail()
+                    .error("Insufficient Balance".to_string())
+                    .status(Status::NotAcceptable)
+                    .clone())
+            }
+        }
+    })
+}
 
-            check_ok_500!(db.create_txn(account.id, txn).await, "Creating Transaction")?;
-            Ok(Response::<String>::new()
-                .message("Transaction Created".to_string())
-                .data(otp.to_string())
-                .status(Status::Ok)
-                .clone())
-        }
-        false => {
-            check_ok_500!(db.create_txn(account.id, txn).await, "Creating Transaction")?;
-            Ok(Response::<String>::new()
+#[post("/account/txn/transfer", data = "<txn>")]
+pub async fn create_txn_transfer(
+    token: TOKEN,
+    db: &State<Repository>,
+    txn: Json<TRANSACTION>,
+    key: IdempotencyKey,
+) -> Result<Response<String>, Status> {
+    check_if_401!(!Type::ACCOUNT.cmp(&token.role.value()));
+    idempotent!(db, key, token.sub, "/account/txn/transfer", *txn, {
+        check_if_406!(txn.destination.is_none());
+        check_if_406!(txn.amount <= 0);
+        let account = db.get_account_from_id(&token.sub).await?;
+        check_if_403!(!account.can_transact());
+        let number = account.number.as_ref().unwrap();
+        let destination = db
+            .get_account(txn.destination.as_ref().unwrap().to_owned())
+            .await?;
+        if !destination.can_receive() {
+            return Ok(Response::<String>::new()
                 .fail()
-                .error("Insufficient Balance".to_string())
+                .error("Destination Cannot Receive".to_string())
                 .status(Status::NotAcceptable)
-                .clone())
+                .clone());
         }
-    }
+        let destination = destination.number.as_ref().unwrap();
+        check_if_406!(destination == number);
+        let mut txn = TRANSACTION::transfer(number, destination, txn.amount);
+        check_result!(
+            db.reject_all_pending_txn("account", &number, account.id, "User")
+                .await,
+            "Reject All Pending Txn"
+        );
+        match txn.is_valid(account.balance) {
+            true => {
+                check_ok_500!(
+                    db.create_txn(account.id, "User", txn).await,
+                    "Creating Transaction"
+                )?;
+                Ok(Response::<String>::new()
+                    .message("Transaction Created".to_string())
+                    .status(Status::Ok)
+                    .clone())
+            }
+            false => {
+                check_ok_500!(
+                    db.create_txn(account.id, "User", txn).await,
+                    "Creating Transaction"
+                )?;
+                Ok(Response::<String>::new()
+                    .fail()
+                    .error("Insufficient Balance".to_string())
+                    .status(Status::NotAcceptable)
+                    .clone())
+            }
+        }
+    })
 }
 
 #[post("/atm/txn/create", data = "<txn>")]
@@ -53,151 +178,283 @@ pub async fn create_txn_atm(
     token: TOKEN,
     db: &State<Repository>,
     txn: Json<TRANSACTION>,
+    key: IdempotencyKey,
 ) -> Result<Response<String>, Status> {
-    let txn_type = txn.txn_type.to_string();
     check_if_401!(!Type::ATM.cmp(&token.role.value()));
-    check_if_406!(txn.account.is_none());
-    let account = db
-        .get_account(txn.account.as_ref().unwrap().to_owned())
-        .await?;
-    let number = account.number.as_ref().unwrap();
-    let atm = db.get_atm_from_id(&token.sub).await?;
-    let amount = txn.amount;
-    let mut txn = TRANSACTION::new(number, &atm.name, &txn_type, amount);
-    check_result!(
-        db.reject_all_pending_txn("atm", &atm.name).await,
-        "Reject All Pending Txn"
-    );
-    match txn.is_valid(account.balance) {
-        true => {
-            let otp = txn.generate_otp().otp.unwrap();
-            check_ok_500!(db.create_txn(account.id, txn).await, "Creating Transaction")?;
-            Ok(Response::<String>::new()
-                .message("Transaction Created".to_string())
-                .data(otp.to_string())
-                .status(Status::Ok)
-                .clone())
-        }
-        false => {
-            check_ok_500!(db.create_txn(account.id, txn).await, "Creating Transaction")?;
-            Ok(Response::<String>::new()
+    let txn_type = txn.txn_type.to_string();
+    idempotent!(db, key, token.sub, "/atm/txn/create", *txn, {
+        check_if_406!(txn.account.is_none());
+        check_if_406!(txn.txn_type == TxnType::TRANSFER);
+        check_if_406!(txn.amount <= 0);
+        let account = db
+            .get_account(txn.account.as_ref().unwrap().to_owned())
+            .await?;
+        check_if_403!(!account.can_transact());
+        let number = account.number.as_ref().unwrap();
+        let atm = db.get_atm_from_id(&token.sub).await?;
+        if atm.current_state(&db.settings) != AtmState::ONLINE {
+            return Ok(Response::<String>::new()
                 .fail()
-                .error("Insufficient Balance".to_string())
+                .error("ATM Not In Service".to_string())
                 .status(Status::NotAcceptable)
-                .clone())
+                .clone());
         }
-    }
+        let amount = txn.amount;
+        if txn.txn_type == TxnType::DEBIT {
+            if !atm.can_dispense(amount) {
+                return Ok(Response::<String>::new()
+                    .fail()
+                    .error("Amount Cannot Be Dispensed".to_string())
+                    .status(Status::NotAcceptable)
+                    .clone());
+            }
+            if let Some(limit) = db.check_txn_limits(number, &atm.name, amount).await? {
+                return Ok(Response::<String>::new()
+                    .fail()
+                    .error(limit.value())
+                    .status(Status::NotAcceptable)
+                    .clone());
+            }
+        }
+        let mut txn = TRANSACTION::new(number, &atm.name, &txn_type, amount);
+        let assessment = db
+            .screen_txn(atm.id, "ATM", &account, &atm, &mut txn)
+            .await?;
+        if assessment.decision == Decision::BLOCK {
+            return Ok(Response::<String>::new()
+                .fail()
+                .error("Transaction Blocked".to_string())
+                .status(Status::Forbidden)
+                .clone());
+        }
+        let challenge = match assessment.decision {
+            Decision::CONFIRM => Some(txn.generate_challenge()?),
+            _ => None,
+        };
+        check_result!(
+            db.reject_all_pending_txn("atm", &atm.name, atm.id, "ATM")
+                .await,
+            "Reject All Pending Txn"
+        );
+        match txn.is_valid(account.balance) {
+            true => {
+                check_ok_500!(
+                    db.create_txn(atm.id, "ATM", txn.clone()).await,
+                    "Creating Transaction"
+                )?;
+                if let Some(challenge) = challenge {
+                    db.send_challenge(&account, &txn, challenge);
+                    return Ok(Response::<String>::new()
+                        .message("Transaction Created, Verification Required".to_string())
+                        .status(Status::Ok)
+                        .clone());
+                }
+                Ok(Response::<String>::new()
+                    .message("Transaction Created".to_string())
+                    .status(Status::Ok)
+                    .clone())
+            }
+            false => {
+                check_ok_500!(
+                    db.create_txn(atm.id, "ATM", txn).await,
+                    "Creating Transaction"
+                )?;
+                Ok(Response::<String>::new()
+                    .fail()
+                    .error("Insufficient Balance".to_string())
+                    .status(Status::NotAcceptable)
+                    .clone())
+            }
+        }
+    })
 }
 
-#[post("/account/txn/confirm/<pin>", data = "<txn>")]
+#[post("/account/txn/confirm", data = "<confirmation>")]
 pub async fn confirm_txn_account(
     token: TOKEN,
     db: &State<Repository>,
-    txn: Json<TRANSACTION>,
-    pin: String,
+    confirmation: Json<Confirmation>,
+    key: IdempotencyKey,
 ) -> Result<Response<String>, Status> {
     check_if_401!(!Type::ACCOUNT.cmp(&token.role.value()));
-    check_if_406!(txn.otp.is_none());
-    let otp = txn.otp.unwrap();
-
-    let pin = check_ok_406!(pin.parse::<i16>())?;
-    let account = db.get_account_from_id(&token.sub).await?;
-    let number = account.number.as_ref().unwrap().to_owned();
-    let mut txn = db.get_pending_txn("account", &number).await?;
+    idempotent!(db, key, token.sub, "/account/txn/confirm", *confirmation, {
+        let account = db.get_account_from_id(&token.sub).await?;
+        check_if_403!(account.pin_temporary || !account.is_active());
+        let number = account.number.as_ref().unwrap().to_owned();
+        let mut txn = db.get_pending_txn("account", &number).await?;
+        // The ATM may have been deactivated or taken out of service since the withdrawal started
+        if let Some(name) = &txn.atm {
+            let atm = db.get_atm(name.to_owned()).await?;
+            check_if_403!(atm.current_state(&db.settings) != AtmState::ONLINE);
+        }
 
-    check_if_406!(!pin == account.pin.unwrap());
-    check_if_406!(otp != txn.otp.unwrap());
-    match txn.is_valid(account.balance) {
-        true => {
-            match txn.txn_type {
-                TxnType::DEBIT => db.debit_amount(number.to_owned(), txn.amount).await?,
-                TxnType::CREDIT => db.credit_amount(number.to_owned(), txn.amount).await?,
+        let id = option!(val -> txn.id; {val} | {return Err(Status::InternalServerError)});
+        let Confirmation {
+            otp,
+            pin,
+            challenge,
+        } = &confirmation.0;
+        if !db.verify_pin(&account, pin).await?
+            || !db.verify_otp(&account, otp).await?
+            || !txn.verify_challenge(challenge.as_deref())?
+        {
+            return match db.record_failed_attempt(&txn, account.id, "User").await? {
+                true => Err(Status::Forbidden),
+                false => Err(Status::NotAcceptable),
+            };
+        }
+        match txn.is_valid(account.balance) {
+            true => match db.settle_txn(&txn, account.id, "User").await {
+                Ok(_) => Ok(Response::<String>::new()
+                    .success()
+                    .status(Status::Ok)
+                    .clone()),
+                Err(error) if error == Status::NotAcceptable => {
+                    check_result!(
+                        db.transition_txn(
+                            id,
+                            TxnStatus::PENDING,
+                            TxnStatus::REJECTED,
+                            account.id,
+                            "User"
+                        )
+                        .await,
+                        "Txn"
+                    );
+                    Ok(Response::<String>::new()
+                        .fail()
+                        .error("Account Balance Insufficient".to_string())
+                        .status(Status::NotAcceptable)
+                        .clone())
+                }
+                Err(error) => Err(error),
+            },
+            false => {
+                check_result!(
+                    db.transition_txn(id, TxnStatus::PENDING, txn.status, account.id, "User")
+                        .await,
+                    "Txn"
+                );
+                Ok(Response::<String>::new()
+                    .fail()
+                    .error("Account Balance Insufficient or Transaction Expired".to_string())
+                    .status(Status::NotAcceptable)
+                    .clone())
             }
-            check_result!(db.confirm_txn("account", &number, "complete").await, "Txn");
-            Ok(Response::<String>::new()
-                .success()
-                .status(Status::Ok)
-                .clone())
-        }
-        false => {
-            check_result!(db.confirm_txn("account", &number, "rejected").await, "Txn");
-            Ok(Response::<String>::new()
-                .fail()
-                .error("Account Balance Insufficient or Transaction Expired".to_string())
-                .status(Status::NotAcceptable)
-                .clone())
         }
-    }
+    })
 }
 
-#[post("/atm/txn/confirm/<pin>", data = "<txn>")]
+#[post("/atm/txn/confirm", data = "<confirmation>")]
 pub async fn confirm_txn_atm(
     token: TOKEN,
     db: &State<Repository>,
-    txn: Json<TRANSACTION>,
-    pin: String,
+    confirmation: Json<Confirmation>,
+    key: IdempotencyKey,
 ) -> Result<Response<String>, Status> {
     check_if_401!(!Type::ATM.cmp(&token.role.value()));
-    check_if_406!(txn.otp.is_none());
-    let otp = txn.otp.unwrap();
+    idempotent!(db, key, token.sub, "/atm/txn/confirm", *confirmation, {
+        let atm = db.get_atm_from_id(&token.sub).await?;
+        check_if_403!(atm.current_state(&db.settings) != AtmState::ONLINE);
+        let mut txn = db.get_pending_txn("atm", &atm.name).await?;
+        let account = db
+            .get_account(txn.account.as_ref().unwrap().to_owned())
+            .await?;
+        check_if_403!(account.pin_temporary || !account.is_active());
 
-    let pin = check_ok_406!(pin.parse::<i16>())?;
-    let atm = db.get_atm_from_id(&token.sub).await?;
-    let mut txn = db.get_pending_txn("atm", &atm.name).await?;
-    let account = db
-        .get_account(txn.account.as_ref().unwrap().to_owned())
-        .await?;
-    let number = account.number.as_ref().unwrap().to_owned();
-
-    check_if_406!(!pin == account.pin.unwrap());
-    check_if_406!(otp != txn.otp.unwrap());
-    match txn.is_valid(account.balance) {
-        true => {
-            match txn.txn_type {
-                TxnType::DEBIT => db.debit_amount(number.to_owned(), txn.amount).await?,
-                TxnType::CREDIT => db.credit_amount(number.to_owned(), txn.amount).await?,
+        let id = option!(val -> txn.id; {val} | {return Err(Status::InternalServerError)});
+        let Confirmation {
+            otp,
+            pin,
+            challenge,
+        } = &confirmation.0;
+        if !db.verify_pin(&account, pin).await?
+            || !db.verify_otp(&account, otp).await?
+            || !txn.verify_challenge(challenge.as_deref())?
+        {
+            return match db.record_failed_attempt(&txn, atm.id, "ATM").await? {
+                true => Err(Status::Forbidden),
+                false => Err(Status::NotAcceptable),
+            };
+        }
+        match txn.is_valid(account.balance) {
+            true => match db.settle_txn(&txn, atm.id, "ATM").await {
+                Ok(_) => Ok(Response::<String>::new()
+                    .success()
+                    .status(Status::Ok)
+                    .clone()),
+                Err(error) if error == Status::NotAcceptable => {
+                    check_result!(
+                        db.transition_txn(
+                            id,
+                            TxnStatus::PENDING,
+                            TxnStatus::REJECTED,
+                            atm.id,
+                            "ATM"
+                        )
+                        .await,
+                        "Txn"
+                    );
+                    Ok(Response::<String>::new()
+                        .fail()
+                        .error("Account Balance Insufficient".to_string())
+                        .status(Status::NotAcceptable)
+                        .clone())
+                }
+                Err(error) => Err(error),
+            },
+            false => {
+                check_result!(
+                    db.transition_txn(id, TxnStatus::PENDING, txn.status, atm.id, "ATM")
+                        .await,
+                    "Txn"
+                );
+                Ok(Response::<String>::new()
+                    .fail()
+                    .status(Status::NotAcceptable)
+                    .clone())
             }
-            check_result!(db.confirm_txn("account", &number, "complete").await, "Txn");
-            Ok(Response::<String>::new()
-                .success()
-                .status(Status::Ok)
-                .clone())
-        }
-        false => {
-            check_result!(db.confirm_txn("account", &number, "rejected").await, "Txn");
-            Ok(Response::<String>::new()
-                .fail()
-                .status(Status::NotAcceptable)
-                .clone())
         }
-    }
+    })
 }
 
 #[get("/account/txn/otp")]
 pub async fn get_otp_account(
     token: TOKEN,
     db: &State<Repository>,
-) -> Result<Response<i16>, Status> {
+) -> Result<Response<String>, Status> {
     check_if_401!(!Type::ACCOUNT.cmp(&token.role.value()));
     let account = db.get_account_from_id(&token.sub).await?;
-    let number = account.number.unwrap();
-    let txn = db.get_pending_txn("account", &number).await?;
-    Ok(Response::<i16>::new()
+    let number = account.number.as_ref().unwrap();
+    db.get_pending_txn("account", number).await?;
+    Ok(Response::<String>::new()
         .success()
-        .data(txn.otp.unwrap())
+        .data(db.get_current_otp(&account).await?)
         .status(Status::Found)
         .clone())
 }
 
-#[get("/atm/txn/otp")]
-pub async fn get_otp_atm(token: TOKEN, db: &State<Repository>) -> Result<Response<i16>, Status> {
-    check_if_401!(!Type::ATM.cmp(&token.role.value()));
-    let atm = db.get_atm_from_id(&token.sub).await?;
-    let txn = db.get_pending_txn("atm", &atm.name).await?;
-    Ok(Response::<i16>::new()
+#[get("/account/totp")]
+pub async fn get_totp_uri(
+    token: TOKEN,
+    db: &State<Repository>,
+) -> Result<Response<String>, Status> {
+    check_if_401!(!Type::ACCOUNT.cmp(&token.role.value()));
+    let account = db.get_account_from_id(&token.sub).await?;
+    let secret = db.get_totp_secret(&account).await?;
+    // The secret never leaves the server again once an authenticator was set up
+    if !db.enroll_totp(&account).await? {
+        return Ok(Response::<String>::new()
+            .fail()
+            .error("TOTP Already Enrolled".to_string())
+            .status(Status::NotAcceptable)
+            .clone());
+    }
+    let uri = account.totp_uri(&secret, &db.settings);
+    Ok(Response::<String>::new()
         .success()
-        .data(txn.otp.unwrap())
-        .status(Status::Found)
+        .data(uri)
+        .status(Status::Ok)
         .clone())
 }
 
@@ -238,9 +495,34 @@ pub async fn reject_atm_txn(
 ) -> Result<Response<String>, Status> {
     check_if_401!(!Type::ATM.cmp(&token.role.value()));
     let atm = db.get_atm_from_id(&token.sub).await?;
-    check_ok_406!(db.reject_all_pending_txn("atm", &atm.name).await)?;
+    check_ok_406!(
+        db.reject_all_pending_txn("atm", &atm.name, atm.id, "ATM")
+            .await
+    )?;
     Ok(Response::<String>::new()
         .success()
         .status(Status::Ok)
         .clone())
 }
+
+#[post("/admin/txn/reverse/<id>", data = "<reversal>")]
+pub async fn reverse_txn_admin(
+    permitted: Permitted<can::TxnReverse>,
+    db: &State<Repository>,
+    reversal: Json<Reversal>,
+    id: String,
+) -> Result<Response<String>, Status> {
+    check_if_400!(reversal.reason.trim().is_empty());
+
+    let txn = db.get_txn_from_id(&id).await?;
+    check_if_406!(txn.reversal_of.is_some());
+    check_if_406!(!TxnStatus::COMPLETE.cmp(&txn.status.value()));
+
+    let admin_id = permitted.id;
+    let reversal_id = db.reverse_txn(admin_id, &txn, reversal.0.reason).await?;
+    Ok(Response::<String>::new()
+        .message("Transaction Reversed".to_string())
+        .data(reversal_id.to_hex())
+        .status(Status::Ok)
+        .clone())
+}
@@ -0,0 +1,68 @@
+use crate::{
+    check_if_400,
+    database::repository::Repository,
+    models::{
+        handlers::Response,
+        helpers::common::timestamp_millis,
+        permission::{can, Permitted},
+        webhook::WEBHOOK,
+    },
+};
+use mongodb::bson::DateTime;
+use rocket::{http::Status, serde::json::Json, State};
+
+#[post("/admin/webhooks", data = "<webhook>")]
+pub async fn create_webhook(
+    permitted: Permitted<can::WebhookManage>,
+    db: &State<Repository>,
+    webhook: Json<WEBHOOK>,
+) -> Result<Response<WEBHOOK>, Status> {
+    check_if_400!(webhook.validate().is_err());
+
+    let admin_id = permitted.id;
+
+    let mut data = webhook.0;
+    let secret = data.generate_secret(&db.keys)?;
+    data.id = None;
+    data.creator = Some(admin_id);
+    data.created = Some(DateTime::from_millis(timestamp_millis()));
+
+    data.id = Some(db.create_webhook(admin_id, data.clone()).await?);
+    data.secret = Some(secret); // Readable only in this response
+    Ok(Response::<WEBHOOK>::new()
+        .message("Webhook Created".to_string())
+        .data(data)
+        .status(Status::Created)
+        .clone())
+}
+
+#[get("/admin/webhooks")]
+pub async fn get_webhooks(
+    _permitted: Permitted<can::WebhookManage>,
+    db: &State<Repository>,
+) -> Result<Response<Vec<WEBHOOK>>, Status> {
+    let mut webhooks = db.get_webhooks().await?;
+    webhooks
+        .iter_mut()
+        .for_each(|webhook| webhook.secret = None);
+    Ok(Response::<Vec<WEBHOOK>>::new()
+        .success()
+        .data(webhooks)
+        .status(Status::Ok)
+        .clone())
+}
+
+#[delete("/admin/webhooks/<id>")]
+pub async fn delete_webhook(
+    permitted: Permitted<can::WebhookManage>,
+    db: &State<Repository>,
+    id: String,
+) -> Result<Response<String>, Status> {
+    let admin_id = permitted.id;
+
+    db.delete_webhook(admin_id, &id).await?;
+    Ok(Response::<String>::new()
+        .message("Webhook Deleted".to_string())
+        .status(Status::Ok)
+        .clone())
+}
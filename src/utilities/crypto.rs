@@ -4,8 +4,10 @@ use jsonwebtoken::{DecodingKey, EncodingKey};
 use rand::{random, thread_rng, Rng};
 use ring::{
     aead::{Aad, BoundKey, Nonce, NonceSequence, OpeningKey, SealingKey, UnboundKey, AES_256_GCM},
+    constant_time::verify_slices_are_equal,
     digest::{Context, SHA256},
     error::Unspecified,
+    hmac::{self, HMAC_SHA1_FOR_LEGACY_USE_ONLY, HMAC_SHA256},
 };
 use std::{
     collections::HashSet,
@@ -88,6 +90,75 @@ pub fn hasher(value: String) -> String {
     to_hex(digest.as_ref())
 }
 
+/// Encodes the given bytes as unpadded RFC 4648 base32, the format authenticator apps expect
+/// # Example
+/// ```
+/// let encoded = to_base32(b"12345678901234567890"); // GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ
+/// ```
+pub fn to_base32(value: &[u8]) -> String {
+    const ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+    let mut encoded = String::new();
+    let (mut buffer, mut bits) = (0u32, 0u32);
+    for byte in value {
+        buffer = (buffer << 8) | *byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            encoded.push(ALPHABET[((buffer >> bits) & 31) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        encoded.push(ALPHABET[((buffer << (5 - bits)) & 31) as usize] as char);
+    }
+    encoded
+}
+
+/// Signs the given value with HMAC-SHA256 and returns the tag as hex
+pub fn sign(secret: &[u8], value: &[u8]) -> String {
+    let key = hmac::Key::new(HMAC_SHA256, secret);
+    to_hex(hmac::sign(&key, value).as_ref())
+}
+
+/// Generates the RFC 6238 code of the given secret for a time step counter
+pub fn totp(secret: &[u8], counter: u64, digits: u32) -> String {
+    let key = hmac::Key::new(HMAC_SHA1_FOR_LEGACY_USE_ONLY, secret);
+    let tag = hmac::sign(&key, &counter.to_be_bytes());
+    let hash = tag.as_ref();
+
+    // Dynamic truncation from RFC 4226
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let binary = (u32::from(hash[offset]) & 0x7f) << 24
+        | u32::from(hash[offset + 1]) << 16
+        | u32::from(hash[offset + 2]) << 8
+        | u32::from(hash[offset + 3]);
+    let code = binary as u64 % 10u64.pow(digits);
+    format!("{:0width$}", code, width = digits as usize)
+}
+
+/// Verifies a code against the steps around the given time, comparing in constant time
+/// Returns the latest step the code belongs to
+pub fn verify_totp(
+    secret: &[u8],
+    code: &str,
+    seconds: u64,
+    step: u64,
+    digits: u32,
+    skew: u64,
+) -> Option<u64> {
+    if code.len() != digits as usize || step == 0 {
+        return None;
+    }
+    let counter = seconds / step;
+    let mut matched = None;
+    for current in counter.saturating_sub(skew)..=counter.saturating_add(skew) {
+        let expected = totp(secret, current, digits);
+        if verify_slices_are_equal(expected.as_bytes(), code.as_bytes()).is_ok() {
+            matched = Some(current);
+        }
+    }
+    matched
+}
+
 /// Encrypts the given value and returns a Result<String, String> where Err holds a string saying Failed to Encrypt
 pub fn encrypt(
     bytes: [u8; 32],
@@ -227,6 +298,10 @@ impl Generator {
         random()
     }
 
+    pub fn generate_totp_secret() -> [u8; 20] {
+        random()
+    }
+
     pub fn generate_sealing_key(
         bytes: &[u8; 32],
         seed: &[u8],
@@ -336,3 +411,59 @@ impl NonceSequence for NonceGenerator {
         ))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{totp, verify_totp};
+
+    // Shared secret of the SHA1 test vectors in RFC 6238 appendix B
+    const SECRET: &[u8] = b"12345678901234567890";
+
+    #[test]
+    fn totp_matches_rfc_6238_vectors() {
+        let vectors = [
+            (59, "94287082"),
+            (1_111_111_109, "07081804"),
+            (1_111_111_111, "14050471"),
+            (1_234_567_890, "89005924"),
+            (2_000_000_000, "69279037"),
+            (20_000_000_000, "65353130"),
+        ];
+        for (seconds, code) in vectors {
+            assert_eq!(totp(SECRET, seconds / 30, 8), code, "at {seconds}");
+        }
+    }
+
+    #[test]
+    fn totp_keeps_leading_zeros() {
+        assert_eq!(totp(SECRET, 1_111_111_109 / 30, 8).len(), 8);
+        assert_eq!(totp(SECRET, 1_111_111_109 / 30, 6), "081804");
+    }
+
+    #[test]
+    fn verify_totp_returns_the_matching_step() {
+        let seconds = 1_111_111_111;
+        let counter = seconds / 30;
+        let code = totp(SECRET, counter, 8);
+        assert_eq!(verify_totp(SECRET, &code, seconds, 30, 8, 1), Some(counter));
+
+        // One step late is inside the skew, two are not
+        let late = seconds + 30;
+        assert_eq!(verify_totp(SECRET, &code, late, 30, 8, 1), Some(counter));
+        assert_eq!(verify_totp(SECRET, &code, late + 30, 30, 8, 1), None);
+    }
+
+    #[test]
+    fn verify_totp_refuses_malformed_codes() {
+        let code = totp(SECRET, 1_111_111_111 / 30, 8);
+        assert_eq!(
+            verify_totp(SECRET, &code[..6], 1_111_111_111, 30, 8, 1),
+            None
+        );
+        assert_eq!(
+            verify_totp(SECRET, "00000000", 1_111_111_111, 30, 8, 0),
+            None
+        );
+        assert_eq!(verify_totp(SECRET, &code, 1_111_111_111, 0, 8, 1), None);
+    }
+}
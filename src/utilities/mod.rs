@@ -1,4 +1,7 @@
 pub mod cors;
 pub mod crypto;
 pub mod macros;
+pub mod notifications;
+pub mod sweeper;
 pub mod time;
+pub mod webhooks;
@@ -0,0 +1,123 @@
+use crate::models::notification::{Channel, NOTIFICATION};
+use rocket::{
+    async_trait,
+    serde::json::to_string,
+    tokio::{fs::OpenOptions, io::AsyncWriteExt, runtime::Handle},
+};
+use std::{env, sync::Arc};
+
+/// Default file the file transport appends to
+pub const DEFAULT_NOTIFICATION_FILE: &str = "notifications.log";
+
+/// Hands a notification to whoever delivers it on its channel
+#[async_trait]
+pub trait Transport: Send + Sync {
+    async fn send(&self, notification: &NOTIFICATION) -> Result<(), String>;
+}
+
+/// Prints the kind and recipient of every notification, never the body which may carry a code
+pub struct LogTransport;
+
+#[async_trait]
+impl Transport for LogTransport {
+    async fn send(&self, notification: &NOTIFICATION) -> Result<(), String> {
+        println!(
+            "[{}] To: {} | {:?}",
+            notification.channel.to_string(),
+            notification.recipient,
+            notification.notice
+        );
+        Ok(())
+    }
+}
+
+/// Appends every notification to a file as one line of json, meant for local testing
+pub struct FileTransport {
+    pub path: String,
+}
+
+#[async_trait]
+impl Transport for FileTransport {
+    async fn send(&self, notification: &NOTIFICATION) -> Result<(), String> {
+        let mut line = to_string(notification).map_err(|error| error.to_string())?;
+        line.push('\n');
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await
+            .map_err(|error| error.to_string())?;
+        file.write_all(line.as_bytes())
+            .await
+            .map_err(|error| error.to_string())
+    }
+}
+
+/// Transport for each channel, channels without one are not notified
+#[derive(Clone, Default)]
+pub struct Notifier {
+    pub email: Option<Arc<dyn Transport>>,
+    pub sms: Option<Arc<dyn Transport>>,
+    pub push: Option<Arc<dyn Transport>>,
+}
+
+fn read_transport(name: &str, default: &str) -> Option<Arc<dyn Transport>> {
+    let value = env::var(name).unwrap_or_else(|_| default.to_string());
+    match value.to_lowercase().as_str() {
+        "log" => Some(Arc::new(LogTransport)),
+        "file" => Some(Arc::new(FileTransport {
+            path: env::var("NOTIFICATION_FILE")
+                .unwrap_or_else(|_| DEFAULT_NOTIFICATION_FILE.to_string()),
+        })),
+        "none" => None,
+        _ => {
+            println!("Unknown transport {value} for {name}, notifications disabled");
+            None
+        }
+    }
+}
+
+impl Notifier {
+    /// Reads NOTIFICATION_TRANSPORT and the EMAIL_, SMS_ and PUSH_TRANSPORT overrides
+    pub fn load() -> Self {
+        let default = env::var("NOTIFICATION_TRANSPORT").unwrap_or_else(|_| "none".to_string());
+        Self {
+            email: read_transport("EMAIL_TRANSPORT", &default),
+            sms: read_transport("SMS_TRANSPORT", &default),
+            push: read_transport("PUSH_TRANSPORT", &default),
+        }
+    }
+
+    pub fn transport(&self, channel: Channel) -> Option<Arc<dyn Transport>> {
+        match channel {
+            Channel::EMAIL => self.email.clone(),
+            Channel::SMS => self.sms.clone(),
+            Channel::PUSH => self.push.clone(),
+        }
+    }
+
+    /// Sends in the background so a slow provider never holds up a request
+    pub fn dispatch(&self, notifications: Vec<NOTIFICATION>) {
+        let handle = match Handle::try_current() {
+            Ok(handle) => handle,
+            Err(_) => {
+                println!("Notifications dropped outside of the runtime");
+                return;
+            }
+        };
+        for notification in notifications {
+            let transport = match self.transport(notification.channel) {
+                Some(transport) => transport,
+                None => continue,
+            };
+            handle.spawn(async move {
+                if let Err(error) = transport.send(&notification).await {
+                    println!(
+                        "Notification over {} failed: {error}",
+                        notification.channel.to_string()
+                    );
+                }
+            });
+        }
+    }
+}
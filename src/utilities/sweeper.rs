@@ -0,0 +1,26 @@
+use crate::database::repository::Repository;
+use rocket::tokio::{self, time::interval};
+use std::{env, time::Duration};
+
+/// Default number of seconds between two sweeps
+pub const DEFAULT_SWEEP_INTERVAL: u64 = 60;
+
+/// Spawns a task that regularly expires PENDING transactions older than TRANSACTION::DEFAULT_EXPIRY
+pub fn spawn_txn_sweeper(repository: Repository) {
+    let seconds = match env::var("SWEEP_INTERVAL") {
+        Ok(value) => value.parse().unwrap_or(DEFAULT_SWEEP_INTERVAL),
+        Err(_) => DEFAULT_SWEEP_INTERVAL,
+    };
+
+    tokio::spawn(async move {
+        let mut ticker = interval(Duration::from_secs(seconds.max(1)));
+        loop {
+            ticker.tick().await;
+            match repository.expire_stale_txn().await {
+                Ok(0) => (),
+                Ok(count) => println!("Sweeper expired {count} transactions"),
+                Err(error) => println!("Sweeper failed: {error}"),
+            }
+        }
+    });
+}
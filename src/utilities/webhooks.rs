@@ -0,0 +1,115 @@
+use crate::{
+    database::repository::Repository,
+    models::{
+        helpers::common::timestamp_millis,
+        webhook::{DELIVERY, WEBHOOK},
+    },
+    utilities::crypto::sign,
+};
+use reqwest::{header::CONTENT_TYPE, redirect::Policy, Client};
+use rocket::{
+    http::Status,
+    tokio::{self, net::lookup_host, time::interval},
+};
+use std::{env, net::SocketAddr, time::Duration};
+
+/// Default number of seconds between two looks at the outbox
+pub const DEFAULT_WEBHOOK_INTERVAL: u64 = 5;
+/// Seconds a receiver gets to answer
+pub const WEBHOOK_TIMEOUT: u64 = 10;
+/// Deliveries sent per look at the outbox
+pub const WEBHOOK_BATCH: i64 = 50;
+
+/// Spawns a task that posts due webhook deliveries and records the outcome
+pub fn spawn_webhook_dispatcher(repository: Repository) {
+    let seconds = match env::var("WEBHOOK_INTERVAL") {
+        Ok(value) => value.parse().unwrap_or(DEFAULT_WEBHOOK_INTERVAL),
+        Err(_) => DEFAULT_WEBHOOK_INTERVAL,
+    };
+    tokio::spawn(async move {
+        let mut ticker = interval(Duration::from_secs(seconds.max(1)));
+        loop {
+            ticker.tick().await;
+            let deliveries = match repository.get_due_deliveries(WEBHOOK_BATCH).await {
+                Ok(deliveries) => deliveries,
+                Err(error) => {
+                    println!("Webhook dispatcher failed: {error}");
+                    continue;
+                }
+            };
+            for delivery in deliveries {
+                let result = deliver(&repository, &delivery).await;
+                if let Err(error) = repository.record_delivery(&delivery, result).await {
+                    println!("Recording delivery failed: {error}");
+                }
+            }
+        }
+    });
+}
+
+/// Resolves the host right before sending, a name that points inside the network is refused
+async fn check_target(webhook: &WEBHOOK) -> Result<(String, Vec<SocketAddr>), String> {
+    let (host, port) = webhook.target()?;
+    let addresses: Vec<SocketAddr> = lookup_host((host.as_str(), port))
+        .await
+        .map_err(|error| error.to_string())?
+        .collect();
+    if addresses.is_empty() {
+        return Err("Host Not Found".to_string());
+    }
+    if addresses
+        .iter()
+        .any(|address| WEBHOOK::is_internal(address.ip()))
+    {
+        return Err("Internal Host Refused".to_string());
+    }
+    Ok((host, addresses))
+}
+
+/// Connects only to the addresses that were checked and never follows a redirect elsewhere
+fn pinned_client(host: &str, addresses: &[SocketAddr]) -> Result<Client, String> {
+    Client::builder()
+        .timeout(Duration::from_secs(WEBHOOK_TIMEOUT))
+        .redirect(Policy::none())
+        .resolve_to_addrs(host, addresses)
+        .build()
+        .map_err(|error| error.to_string())
+}
+
+async fn deliver(repository: &Repository, delivery: &DELIVERY) -> Result<(), String> {
+    let webhook = match repository
+        .get_webhook_from_id(&delivery.webhook.to_hex())
+        .await
+    {
+        Ok(webhook) => webhook,
+        Err(error) if error == Status::NotFound => return Err("Webhook Removed".to_string()),
+        Err(error) => return Err(error.to_string()),
+    };
+    let secret = match webhook.secret(&repository.keys) {
+        Ok(secret) => secret,
+        Err(error) => return Err(error.to_string()),
+    };
+    let (host, addresses) = check_target(&webhook).await?;
+    let client = pinned_client(&host, &addresses)?;
+    // The timestamp is signed along with the body so an old delivery cannot be replayed
+    let timestamp = timestamp_millis();
+    let signed = format!("{timestamp}.{}", delivery.payload);
+    let signature = sign(secret.as_bytes(), signed.as_bytes());
+    let id = delivery.id.map(|id| id.to_hex()).unwrap_or_default();
+
+    let response = client
+        .post(&webhook.url)
+        .header(CONTENT_TYPE, "application/json")
+        .header(WEBHOOK::SIGNATURE_HEADER, format!("sha256={signature}"))
+        .header(WEBHOOK::TIMESTAMP_HEADER, timestamp.to_string())
+        .header(WEBHOOK::EVENT_HEADER, &delivery.event)
+        .header(WEBHOOK::DELIVERY_HEADER, id)
+        .body(delivery.payload.to_owned())
+        .send()
+        .await;
+    match response {
+        Ok(response) if response.status().is_success() => Ok(()),
+        Ok(response) => Err(format!("Receiver answered {}", response.status())),
+        Err(error) => Err(error.to_string()),
+    }
+}